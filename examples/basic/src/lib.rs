@@ -1,15 +1,15 @@
 //! Basic tos-alloc example (Heap-Based Allocator)
 //!
-//! Demonstrates Vec and BTreeMap usage with heap-based dynamic memory allocation
+//! Demonstrates Vec and VecMap usage with heap-based dynamic memory allocation
 
 #![no_std]
 #![no_main]
 
 extern crate alloc;
 use alloc::vec::Vec;
-use alloc::collections::BTreeMap;
 
 use tako_sdk::*;
+use tos_alloc::collections::VecMap;
 use tos_alloc::TosAllocator;
 
 #[global_allocator]
@@ -47,9 +47,9 @@ pub extern "C" fn entrypoint() -> u64 {
     }
     log_u64(numbers.len() as u64, 0, 0, 0, 0);
 
-    // Test 2: BTreeMap operations
-    log("Test 2: BTreeMap operations");
-    let mut map = BTreeMap::new();
+    // Test 2: VecMap operations (a BTreeMap in one heap block)
+    log("Test 2: VecMap operations");
+    let mut map = VecMap::new();
     map.insert(1u32, 100u32);
     map.insert(2u32, 200u32);
     map.insert(3u32, 300u32);
@@ -57,7 +57,7 @@ pub extern "C" fn entrypoint() -> u64 {
 
     // Test 3: Heap usage
     log("Test 3: Heap usage");
-    let (used, remaining) = TosAllocator::usage();
+    let (used, remaining) = ALLOCATOR.usage();
     log_u64(used as u64, remaining as u64, 0, 0, 0);
 
     log("=== All tests passed ===");
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tos_alloc_fuzz::{run, Op};
+
+fuzz_target!(|ops: Vec<Op>| run(&ops));
@@ -0,0 +1,230 @@
+//! Fuzz driver shared by all targets
+//!
+//! Replays an arbitrary sequence of `GlobalAlloc` calls against the backend
+//! selected by cargo feature, over a `HostHeap`. Every returned block must lie
+//! in the heap past the allocator state (`header_len` in the heap header), be
+//! aligned and not overlap a live block; the heap header, the contents of live
+//! blocks and the `usage()` totals must survive every call.
+//!
+//! `GlobalAlloc` requires non-zero sizes, so zero-size requests are dropped
+//! before they reach the allocator, as the standard collections never make
+//! them.
+
+use arbitrary::Arbitrary;
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{HeapHeader, HeapRegion, HostHeap, TosAllocator};
+
+const HEAP_SIZE: usize = 16 * 1024;
+
+/// Most blocks tracked at once, keeping each input cheap to replay
+const MAX_LIVE: usize = 256;
+
+/// Requested size, biased toward what fits the heap
+#[derive(Arbitrary, Debug)]
+pub enum Size {
+    Small(u8),
+    Medium(u16),
+    Any(usize),
+}
+
+impl Size {
+    fn get(&self) -> usize {
+        match *self {
+            Size::Small(size) => size as usize,
+            Size::Medium(size) => size as usize,
+            Size::Any(size) => size,
+        }
+    }
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Op {
+    /// Allocate with alignment `1 << align_log2` (any power of two); zero
+    /// sizes are skipped
+    Alloc { size: Size, align_log2: u8 },
+    /// Allocate zeroed memory, which must read as zero
+    AllocZeroed { size: Size, align_log2: u8 },
+    /// Free the live block at `index` (modulo the number of live blocks)
+    Dealloc { index: u8 },
+    /// Resize the live block at `index`; zero sizes are skipped
+    Realloc { index: u8, new_size: Size },
+}
+
+struct Block {
+    ptr: *mut u8,
+    layout: Layout,
+    fill: u8,
+}
+
+struct State<'a> {
+    heap: &'a HostHeap,
+    allocator: TosAllocator<&'a HostHeap>,
+    blocks: Vec<Block>,
+    total: usize,
+    next_fill: u8,
+    /// Heap header as laid out by the first allocation
+    header: Option<HeapHeader>,
+}
+
+impl State<'_> {
+    unsafe fn insert(&mut self, ptr: *mut u8, layout: Layout) {
+        // A block was handed out, so the heap is laid out
+        let header = *self.header.get_or_insert_with(|| {
+            self.allocator
+                .heap_header()
+                .expect("block handed out before the heap was initialized")
+        });
+        let addr = ptr as usize;
+        let start = self.heap.start();
+        assert!(
+            addr >= start + header.header_len as usize,
+            "{addr:#x} covers the {} header bytes",
+            header.header_len
+        );
+        assert!(
+            addr.checked_add(layout.size())
+                .is_some_and(|end| end <= start + self.heap.size()),
+            "{addr:#x} {layout:?} beyond heap"
+        );
+        assert_eq!(addr % layout.align(), 0, "{addr:#x} misaligned {layout:?}");
+        for block in &self.blocks {
+            let other = block.ptr as usize;
+            assert!(
+                addr + layout.size() <= other || other + block.layout.size() <= addr,
+                "{addr:#x} {layout:?} overlaps {other:#x} {:?}",
+                block.layout
+            );
+        }
+
+        self.next_fill = self.next_fill.wrapping_add(1).max(1);
+        ptr.write_bytes(self.next_fill, layout.size());
+        self.blocks.push(Block {
+            ptr,
+            layout,
+            fill: self.next_fill,
+        });
+    }
+
+    unsafe fn verify(&self) {
+        for block in &self.blocks {
+            let bytes = core::slice::from_raw_parts(block.ptr, block.layout.size());
+            assert!(
+                bytes.iter().all(|&byte| byte == block.fill),
+                "block {:p} {:?} corrupted",
+                block.ptr,
+                block.layout
+            );
+        }
+
+        // Only the failure code may change once the heap is laid out
+        if let Some(header) = self.header {
+            let current = HeapHeader::read(self.heap.as_slice()).expect("heap header overwritten");
+            assert_eq!(
+                HeapHeader {
+                    failure: header.failure,
+                    ..current
+                },
+                header,
+                "heap header changed"
+            );
+            assert_eq!(self.allocator.heap_header(), Some(current));
+        }
+
+        let (used, remaining) = self.allocator.usage();
+        assert_eq!(used + remaining, self.total, "allocator state corrupted");
+    }
+
+    fn pick(&self, index: u8) -> Option<usize> {
+        match self.blocks.len() {
+            0 => None,
+            len => Some(index as usize % len),
+        }
+    }
+}
+
+/// Replay `ops`, panicking on the first broken invariant
+pub fn run(ops: &[Op]) {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    let (used, total) = allocator.usage();
+    assert_eq!(used, 0);
+    let mut state = State {
+        heap: &heap,
+        allocator,
+        blocks: Vec::new(),
+        total,
+        next_fill: 0,
+        header: None,
+    };
+
+    unsafe {
+        for op in ops {
+            match op {
+                Op::Alloc { size, align_log2 } | Op::AllocZeroed { size, align_log2 } => {
+                    let align = 1usize.checked_shl(*align_log2 as u32).unwrap_or(0);
+                    let Ok(layout) = Layout::from_size_align(size.get(), align) else {
+                        continue;
+                    };
+                    if layout.size() == 0 || state.blocks.len() == MAX_LIVE {
+                        continue;
+                    }
+                    let zeroed = matches!(op, Op::AllocZeroed { .. });
+                    let ptr = if zeroed {
+                        state.allocator.alloc_zeroed(layout)
+                    } else {
+                        state.allocator.alloc(layout)
+                    };
+                    if ptr.is_null() {
+                        continue;
+                    }
+                    if zeroed {
+                        let bytes = core::slice::from_raw_parts(ptr, layout.size());
+                        assert!(bytes.iter().all(|&byte| byte == 0), "{layout:?} not zeroed");
+                    }
+                    state.insert(ptr, layout);
+                }
+                Op::Dealloc { index } => {
+                    let Some(index) = state.pick(*index) else {
+                        continue;
+                    };
+                    let block = state.blocks.remove(index);
+                    state.allocator.dealloc(block.ptr, block.layout);
+                }
+                Op::Realloc { index, new_size } => {
+                    let Some(index) = state.pick(*index) else {
+                        continue;
+                    };
+                    let align = state.blocks[index].layout.align();
+                    let new_size = new_size.get();
+                    // Callers must pass a non-zero size that forms a valid layout
+                    let Ok(layout) = Layout::from_size_align(new_size, align) else {
+                        continue;
+                    };
+                    if new_size == 0 {
+                        continue;
+                    }
+
+                    let block = state.blocks.remove(index);
+                    let ptr = state.allocator.realloc(block.ptr, block.layout, new_size);
+                    if ptr.is_null() {
+                        state.blocks.insert(index, block);
+                    } else {
+                        let kept = block.layout.size().min(new_size);
+                        let bytes = core::slice::from_raw_parts(ptr, kept);
+                        assert!(
+                            bytes.iter().all(|&byte| byte == block.fill),
+                            "realloc lost contents"
+                        );
+                        state.insert(ptr, layout);
+                    }
+                }
+            }
+            state.verify();
+        }
+
+        while let Some(block) = state.blocks.pop() {
+            state.allocator.dealloc(block.ptr, block.layout);
+            state.verify();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Operations shared by every backend
+//!
+//! Backends implement [`Backend`]; their `GlobalAlloc` impls forward to the
+//! functions here, which wrap each call with the bookkeeping common to all
+//! backends: allocation statistics with the `stats` feature, failure reports
+//! with `oom-diagnostics`, and the allocator's [`OomPolicy`].
+
+use core::alloc::Layout;
+use core::ptr::{copy_nonoverlapping, write_bytes};
+
+#[cfg(feature = "oom-diagnostics")]
+use crate::diagnostics;
+use crate::failure::AllocFailure;
+use crate::oom::OomPolicy;
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+/// Raw allocation operations of a backend
+pub(crate) trait Backend {
+    /// Allocate a block for `layout`, or return null
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// Allocate a zeroed block for `layout`, or return null
+    ///
+    /// Backends that know which memory was never handed out since the heap
+    /// was zeroed override this to clear only what was.
+    unsafe fn allocate_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.allocate(layout);
+        if !ptr.is_null() {
+            write_bytes(ptr, 0, layout.size());
+        }
+        ptr
+    }
+
+    /// Free a block returned by `allocate` or `reallocate`
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout);
+
+    /// Resize a block, moving it if needed, or return null and keep it
+    unsafe fn reallocate(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.allocate(new_layout);
+        if !new_ptr.is_null() {
+            copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.deallocate(ptr, layout);
+        }
+        new_ptr
+    }
+
+    /// Bytes in use, as reported by `usage()`, 0 until the heap is
+    /// initialized
+    #[cfg(feature = "stats")]
+    fn used(&self) -> usize;
+
+    /// Statistics in the heap header, null until the heap is initialized
+    #[cfg(feature = "stats")]
+    fn stats_mut(&self) -> *mut AllocStats;
+
+    /// Why the most recent failed request returned null
+    fn failure(&self) -> Option<AllocFailure>;
+
+    /// What to do when a request fails
+    fn oom_policy(&self) -> OomPolicy;
+}
+
+#[inline]
+pub(crate) unsafe fn alloc<B: Backend>(backend: &B, layout: Layout) -> *mut u8 {
+    alloc_with(backend, layout, false)
+}
+
+#[inline]
+pub(crate) unsafe fn alloc_zeroed<B: Backend>(backend: &B, layout: Layout) -> *mut u8 {
+    alloc_with(backend, layout, true)
+}
+
+#[inline(always)]
+unsafe fn alloc_with<B: Backend>(backend: &B, layout: Layout, zeroed: bool) -> *mut u8 {
+    loop {
+        #[cfg(feature = "stats")]
+        let before = backend.used();
+        let ptr = if zeroed {
+            backend.allocate_zeroed(layout)
+        } else {
+            backend.allocate(layout)
+        };
+        #[cfg(feature = "stats")]
+        if let Some(stats) = backend.stats_mut().as_mut() {
+            stats.record_alloc(ptr, &layout, before, backend.used());
+        }
+        if !ptr.is_null() || !out_of_memory(backend, layout) {
+            return ptr;
+        }
+    }
+}
+
+#[inline]
+pub(crate) unsafe fn dealloc<B: Backend>(backend: &B, ptr: *mut u8, layout: Layout) {
+    backend.deallocate(ptr, layout);
+    #[cfg(feature = "stats")]
+    if let Some(stats) = backend.stats_mut().as_mut() {
+        stats.record_dealloc();
+    }
+}
+
+#[inline]
+pub(crate) unsafe fn realloc<B: Backend>(
+    backend: &B,
+    ptr: *mut u8,
+    layout: Layout,
+    new_size: usize,
+) -> *mut u8 {
+    let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+    loop {
+        let new_ptr = backend.reallocate(ptr, layout, new_size);
+        #[cfg(feature = "stats")]
+        if let Some(stats) = backend.stats_mut().as_mut() {
+            stats.record_realloc(new_ptr, &new_layout, backend.used());
+        }
+        if !new_ptr.is_null() || !out_of_memory(backend, new_layout) {
+            return new_ptr;
+        }
+    }
+}
+
+/// Report a request that returned null and apply the OOM policy; `true`
+/// to retry it
+#[cold]
+unsafe fn out_of_memory<B: Backend>(backend: &B, layout: Layout) -> bool {
+    let failure = backend.failure();
+    #[cfg(feature = "oom-diagnostics")]
+    {
+        let peak = backend.stats_mut().as_ref().map_or(0, |stats| stats.peak);
+        diagnostics::report(failure, &layout, backend.used(), peak);
+    }
+    let failure = failure.unwrap_or(AllocFailure::OutOfMemory);
+    backend.oom_policy().handle(layout, failure)
+}
@@ -0,0 +1,426 @@
+//! Buddy allocator for TOS TAKO VM
+//!
+//! Requests are rounded up to a power of two and served from blocks obtained
+//! by repeatedly halving larger ones. A freed block merges with its "buddy"
+//! (the other half of its parent) whenever both are free, so coalescing is a
+//! single XOR and bitmap test per level. Heap sizes are powers of two
+//! (`DEFAULT_HEAP_SIZE`, `MAX_HEAP_SIZE`), which makes this a natural fit.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+#[cfg(feature = "stats")]
+use core::ptr::null_mut;
+
+use crate::backend::{self, Backend};
+use crate::constants::MAX_HEAP_SIZE;
+use crate::failure::{check_layout, fail, AllocFailure};
+use crate::header::{BackendId, HeapHeader};
+use crate::oom::OomPolicy;
+use crate::region::{heap_size, HeapRegion, SyscallRegion};
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+/// log2 of the smallest block (16 bytes, room for the free-list links)
+const MIN_ORDER: u32 = 4;
+const MIN_BLOCK_SIZE: usize = 1 << MIN_ORDER;
+
+/// log2 of the largest block (the whole of `MAX_HEAP_SIZE`)
+const MAX_ORDER: u32 = MAX_HEAP_SIZE.trailing_zeros();
+
+const ORDER_COUNT: usize = (MAX_ORDER - MIN_ORDER + 1) as usize;
+
+const BACKEND: BackendId = BackendId::Buddy;
+
+/// Allocator state stored at the start of the heap
+///
+/// Followed by the free bitmap: one bit per `MIN_BLOCK_SIZE` chunk, set when
+/// a free block starts there. Block references are `u32` offsets from heap
+/// start; offset 0 is the header and doubles as the null reference.
+#[repr(C)]
+struct Header {
+    heap: HeapHeader,
+    #[cfg(feature = "stats")]
+    stats: AllocStats,
+    /// Bytes in allocated blocks (rounded up to powers of two)
+    used: usize,
+    /// Bytes managed as blocks, measured from heap start
+    span: u32,
+    /// Bit `i` set when the free list for order `MIN_ORDER + i` is non-empty
+    nonempty: u32,
+    /// Free-list head per order
+    free: [u32; ORDER_COUNT],
+}
+
+/// Free block, stored in the freed memory itself
+#[repr(C)]
+struct FreeBlock {
+    next: u32,
+    prev: u32,
+    /// Order of this block, valid while its free bit is set
+    order: u32,
+}
+
+/// Bytes managed as blocks in a heap of `len` bytes
+#[inline]
+fn span_of(len: usize) -> usize {
+    len & !(MIN_BLOCK_SIZE - 1)
+}
+
+/// Bytes taken by the header and a bitmap covering `span`, never handed out
+#[inline]
+fn reserved(span: usize) -> usize {
+    let bitmap = span / MIN_BLOCK_SIZE / 8;
+    (size_of::<Header>() + bitmap + MIN_BLOCK_SIZE - 1) & !(MIN_BLOCK_SIZE - 1)
+}
+
+/// Order of the block serving `layout`, if any
+#[inline]
+fn block_order(layout: &Layout) -> Option<u32> {
+    let size = layout.size().max(layout.align()).max(MIN_BLOCK_SIZE);
+    if size > MAX_HEAP_SIZE {
+        return None;
+    }
+    Some(size.next_power_of_two().trailing_zeros())
+}
+
+/// Heap-based buddy allocator
+///
+/// **Key design decisions**:
+/// 1. **State on the heap** - Free-list heads and the free bitmap live at
+///    heap start, like `BumpAllocator`'s position pointer. The heap size
+///    granted by the VM is discovered on first use
+/// 2. **Power-of-two blocks** - Every block is aligned to its own size
+///    (relative to heap start), so alignment comes for free
+/// 3. **Cheap coalescing** - A block's buddy is found by flipping one
+///    offset bit; the bitmap tells whether it is free
+///
+/// Heap start must be aligned to the largest alignment requested; the TAKO
+/// heap at 0x300000000 satisfies any alignment up to `MAX_HEAP_SIZE`.
+///
+/// # Heap Layout
+///
+/// ```text
+/// 0x300000000: HeapHeader (magic, backend, VM heap region)
+/// 0x300000020: Used bytes, free-list heads
+/// 0x300000070: Free bitmap (1 bit per 16 bytes of heap)
+/// 0x300000170: Blocks (power-of-two sizes, aligned to their size)
+/// ...          (the bitmap grows with the heap, 2 KB for 256 KB)
+/// 0x300008000: Heap top
+/// ```
+///
+/// # Usage
+///
+/// Enable the `buddy` feature (and disable the default `bump` feature):
+///
+/// ```toml
+/// tos-alloc = { path = "../../tos-alloc", default-features = false, features = ["buddy"] }
+/// ```
+///
+/// `TosAllocator` then refers to this allocator, so contract code is unchanged.
+pub struct BuddyAllocator<R = SyscallRegion> {
+    region: R,
+    policy: OomPolicy,
+}
+
+impl<const START: usize, const FALLBACK: usize> BuddyAllocator<SyscallRegion<START, FALLBACK>> {
+    /// Create a new allocator for the heap granted by the VM
+    pub const fn new() -> Self {
+        Self::with_region(SyscallRegion::new())
+    }
+}
+
+impl<R: HeapRegion> BuddyAllocator<R> {
+    /// Create a new allocator managing `region`
+    pub const fn with_region(region: R) -> Self {
+        Self {
+            region,
+            policy: OomPolicy::Null,
+        }
+    }
+
+    /// Handle failed requests with `policy` instead of returning null
+    pub const fn with_oom_policy(mut self, policy: OomPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get heap usage statistics
+    ///
+    /// Returns (used_bytes, remaining_bytes). Used bytes count whole
+    /// power-of-two blocks; remaining bytes may be fragmented.
+    pub fn usage(&self) -> (usize, usize) {
+        unsafe {
+            let (used, span) = if (*self.heap()).is_owned_by(BACKEND) {
+                let header = &*self.header();
+                (header.used, header.span as usize)
+            } else {
+                (0, span_of(heap_size(&self.region)))
+            };
+            // Heaps too small for the allocator state have no usable space
+            let usable = span.saturating_sub(reserved(span));
+            (used, usable - used)
+        }
+    }
+
+    /// Header describing the heap, once the first allocation initialized it
+    pub fn heap_header(&self) -> Option<HeapHeader> {
+        let heap = unsafe { &*self.heap() };
+        heap.is_owned_by(BACKEND).then_some(*heap)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        unsafe { (*self.heap()).last_failure_of(BACKEND) }
+    }
+
+    /// Allocation statistics since the heap was initialized
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        unsafe { self.stats_mut().as_ref().copied().unwrap_or_default() }
+    }
+
+    /// Heap header, which every region is large enough to hold
+    #[inline]
+    fn heap(&self) -> *mut HeapHeader {
+        self.region.start() as *mut HeapHeader
+    }
+
+    /// Allocator state, only valid once the heap header says this
+    /// allocator laid it out
+    #[inline]
+    fn header(&self) -> *mut Header {
+        self.region.start() as *mut Header
+    }
+
+    #[inline]
+    fn block(&self, offset: u32) -> *mut FreeBlock {
+        (self.region.start() + offset as usize) as *mut FreeBlock
+    }
+
+    /// Byte and mask of the free bit for the block at `offset`
+    #[inline]
+    fn free_bit(&self, offset: u32) -> (*mut u8, u8) {
+        let chunk = offset as usize / MIN_BLOCK_SIZE;
+        let byte = (self.region.start() + size_of::<Header>() + chunk / 8) as *mut u8;
+        (byte, 1 << (chunk % 8))
+    }
+
+    /// Split the usable range into maximal aligned blocks
+    unsafe fn init(&self, header: &mut Header, len: usize) {
+        let start = self.region.start();
+        let span = span_of(len);
+        header.span = span as u32;
+        let mut offset = reserved(span);
+        header.heap.init(BACKEND, offset, start, len);
+
+        while offset + MIN_BLOCK_SIZE <= span {
+            let mut order = offset.trailing_zeros().min(MAX_ORDER);
+            while offset + (1 << order) > span {
+                order -= 1;
+            }
+            self.push(header, offset as u32, order);
+            offset += 1 << order;
+        }
+    }
+
+    /// Allocator state, laid out on first use
+    ///
+    /// Nothing past the heap header is read or written unless the region
+    /// can hold the whole state.
+    #[inline]
+    unsafe fn state(&self) -> Result<*mut Header, AllocFailure> {
+        if (*self.heap()).needs_init(BACKEND)? {
+            let len = heap_size(&self.region);
+            if len < size_of::<Header>() {
+                return Err(AllocFailure::SizeTooLarge);
+            }
+            // Statistics under a zeroed heap header mean it was wiped
+            #[cfg(feature = "stats")]
+            if (*self.header()).stats != AllocStats::default() {
+                return Err(AllocFailure::HeapCorrupted);
+            }
+            self.init(&mut *self.header(), len);
+        }
+        Ok(self.header())
+    }
+
+    unsafe fn push(&self, header: &mut Header, offset: u32, order: u32) {
+        let index = (order - MIN_ORDER) as usize;
+        let head = header.free[index];
+        let block = self.block(offset);
+
+        (*block).next = head;
+        (*block).prev = 0;
+        (*block).order = order;
+        if head != 0 {
+            (*self.block(head)).prev = offset;
+        }
+        header.free[index] = offset;
+        header.nonempty |= 1 << index;
+
+        let (byte, mask) = self.free_bit(offset);
+        *byte |= mask;
+    }
+
+    unsafe fn remove(&self, header: &mut Header, offset: u32) {
+        let block = self.block(offset);
+        let index = ((*block).order - MIN_ORDER) as usize;
+        let next = (*block).next;
+        let prev = (*block).prev;
+
+        if next != 0 {
+            (*self.block(next)).prev = prev;
+        }
+        if prev != 0 {
+            (*self.block(prev)).next = next;
+        } else {
+            header.free[index] = next;
+            if next == 0 {
+                header.nonempty &= !(1 << index);
+            }
+        }
+
+        let (byte, mask) = self.free_bit(offset);
+        *byte &= !mask;
+    }
+
+    /// Whether a free block of exactly `order` starts at `offset`
+    #[inline]
+    unsafe fn is_free(&self, header: &Header, offset: u32, order: u32) -> bool {
+        if offset as usize + (1 << order) > header.span as usize {
+            return false;
+        }
+        let (byte, mask) = self.free_bit(offset);
+        *byte & mask != 0 && (*self.block(offset)).order == order
+    }
+}
+
+impl<R: HeapRegion + Default> Default for BuddyAllocator<R> {
+    fn default() -> Self {
+        Self::with_region(R::default())
+    }
+}
+
+impl<R: HeapRegion> Backend for BuddyAllocator<R> {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let header = match self.state() {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        let span = header.span as usize;
+        if let Err(failure) = check_layout(&layout, span.saturating_sub(reserved(span))) {
+            return fail(&mut header.heap, failure);
+        }
+
+        let order = match block_order(&layout) {
+            Some(order) => order,
+            None => return fail(&mut header.heap, AllocFailure::SizeTooLarge),
+        };
+
+        // Blocks are aligned relative to heap start only
+        if self.region.start() & (layout.align() - 1) != 0 {
+            return fail(&mut header.heap, AllocFailure::AlignTooLarge);
+        }
+
+        // Smallest non-empty free list that can hold the request
+        let candidates = header.nonempty >> (order - MIN_ORDER);
+        if candidates == 0 {
+            return fail(&mut header.heap, AllocFailure::OutOfMemory);
+        }
+        let mut current = order + candidates.trailing_zeros();
+        let offset = header.free[(current - MIN_ORDER) as usize];
+        self.remove(header, offset);
+
+        // Split, returning the upper halves to the free lists
+        while current > order {
+            current -= 1;
+            self.push(header, offset + (1 << current), current);
+        }
+
+        header.used += 1 << order;
+        (self.region.start() + offset as usize) as *mut u8
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+        let mut order = match block_order(&layout) {
+            Some(order) => order,
+            None => return,
+        };
+        let mut offset = (ptr as usize - self.region.start()) as u32;
+        header.used -= 1 << order;
+
+        // Merge with the buddy for as long as it is free
+        while order < MAX_ORDER {
+            let buddy = offset ^ (1 << order);
+            if !self.is_free(header, buddy, order) {
+                break;
+            }
+            self.remove(header, buddy);
+            offset = offset.min(buddy);
+            order += 1;
+        }
+
+        self.push(header, offset, order);
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn used(&self) -> usize {
+        // Read from the header, so recording stats never queries the region
+        if unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            unsafe { (*self.header()).used }
+        } else {
+            0
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn stats_mut(&self) -> *mut AllocStats {
+        let header = self.header();
+        unsafe {
+            if (*self.heap()).is_owned_by(BACKEND) {
+                &mut (*header).stats
+            } else {
+                null_mut()
+            }
+        }
+    }
+
+    #[inline]
+    fn failure(&self) -> Option<AllocFailure> {
+        self.last_failure()
+    }
+
+    #[inline]
+    fn oom_policy(&self) -> OomPolicy {
+        self.policy
+    }
+}
+
+unsafe impl<R: HeapRegion> GlobalAlloc for BuddyAllocator<R> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        backend::alloc(self, layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        backend::alloc_zeroed(self, layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        backend::dealloc(self, ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        backend::realloc(self, ptr, layout, new_size)
+    }
+}
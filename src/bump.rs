@@ -3,31 +3,98 @@
 //! This implementation matches Solana's allocator exactly to ensure compatibility.
 
 use core::alloc::{GlobalAlloc, Layout};
+use core::cell::Cell;
 use core::mem::size_of;
+#[cfg(not(feature = "bump-upward"))]
+use core::ptr::copy;
+#[cfg(feature = "stats")]
 use core::ptr::null_mut;
+use core::ptr::{copy_nonoverlapping, write_bytes, NonNull};
 
-/// Heap start address (matches Solana's MM_HEAP_START)
-pub const HEAP_START_ADDRESS: usize = 0x300000000;
+use crate::backend::{self, Backend};
+use crate::failure::{check_layout, fail, AllocFailure};
+use crate::header::{BackendId, HeapHeader};
+use crate::oom::OomPolicy;
+use crate::region::{heap_size, HeapRegion, SyscallRegion};
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
 
-/// Default heap size (32 KB, matches Solana)
-pub const HEAP_LENGTH: usize = 32 * 1024;
+/// Granularity of the position; block sizes are rounded up to it
+const WORD: usize = size_of::<usize>();
+
+/// Round a block size up to whole words
+#[inline]
+fn word_size(size: usize) -> usize {
+    (size + WORD - 1) & !(WORD - 1)
+}
+
+#[cfg(not(feature = "bump-upward"))]
+const BACKEND: BackendId = BackendId::Bump;
+#[cfg(feature = "bump-upward")]
+const BACKEND: BackendId = BackendId::BumpUpward;
+
+/// Allocator state stored at the start of the heap
+#[repr(C)]
+struct Header {
+    heap: HeapHeader,
+    #[cfg(feature = "stats")]
+    stats: AllocStats,
+    /// Current allocation position
+    pos: usize,
+    /// Heap top, rounded down to whole words
+    end: usize,
+    /// Furthest position so far; memory past it was never handed out and
+    /// is still zero
+    fresh: usize,
+}
 
 /// Solana-compatible bump allocator
 ///
+/// The heap comes from a [`HeapRegion`]. The default, [`SyscallRegion`], asks
+/// the TAKO VM, so `BumpAllocator` alone is the usual choice; other regions
+/// are picked with [`BumpAllocator::with_region`].
+///
 /// **Key design decisions (matching Solana exactly)**:
-/// 1. **Hardcoded heap address** (0x300000000) - No syscalls needed
+/// 1. **Heap region from the VM** - The region's size (for `SyscallRegion`,
+///    `tos_get_heap_region`) is queried once, on the first allocation, and
+///    the heap top is cached in the header. The heap is capped at
+///    `MAX_HEAP_SIZE`
 /// 2. **Allocates from high to low** - Position starts at heap_top, moves down
-/// 3. **Position pointer at heap start** - First 8 bytes store current position
+/// 3. **State at heap start** - The common [`HeapHeader`] is followed by the
+///    current position
+/// 4. **LIFO reclaim** - Freeing the most recent allocation moves the position
+///    back up; any other `dealloc` is a no-op. Block sizes are rounded up to
+///    whole words so the position stays word aligned and a chain of frees
+///    reclaims everything. Padding in front of an over-aligned block (align
+///    above 8) cannot be recovered, so reclaim stops below such a block
+/// 5. **Block reuse on realloc** - Shrinking is always in place. Growing the
+///    most recent allocation extends its block downward instead of leaking
+///    it, which saves heap but still copies the contents to the new start;
+///    only `bump-upward` grows without copying
+/// 6. **Zeroing only reused memory** - The heap starts zeroed, so
+///    `alloc_zeroed` clears only the part of a block that was handed out
+///    before and skips the memset for memory never used
 ///
 /// # Heap Layout
 ///
 /// ```text
-/// 0x300000000: Position Pointer (8 bytes) ← Stores current allocation position
-/// 0x300000008: User allocations start here
-/// ...          Allocations grow upward
-/// 0x300008000: Heap top (initial position value)
+/// 0x300000000: HeapHeader (32 bytes)      ← Magic, backend, VM heap region
+/// 0x300000020: Position Pointer (8 bytes) ← Stores current allocation position
+/// 0x300000028: Heap Top (8 bytes)         ← Cached VM heap region
+/// 0x300000030: Fresh Mark (8 bytes)       ← Lowest position ever reached
+/// 0x300000038: Lowest usable address
+/// ...          Allocations grow downward
+/// 0x300008000: Heap top (initial position value, 32 KB heap)
 /// ```
 ///
+/// # Upward Mode
+///
+/// With the `bump-upward` feature, the position starts at 0x300000038 and
+/// allocations grow toward the heap top instead. This is not Solana
+/// compatible, but the most recent allocation can then be grown (or shrunk)
+/// in place by `realloc` without moving its contents, and allocations appear
+/// in address order in heap dumps.
+///
 /// # Usage
 ///
 /// ```rust,no_run
@@ -37,88 +104,585 @@ pub const HEAP_LENGTH: usize = 32 * 1024;
 /// static ALLOCATOR: TosAllocator = TosAllocator::new();
 ///
 /// let v = vec![1, 2, 3];  // Works immediately
+/// let (used, remaining) = ALLOCATOR.usage();
 /// ```
-pub struct BumpAllocator {
-    pub start: usize,
-    pub len: usize,
+///
+/// A different heap length can be picked with [`declare_allocator!`](crate::declare_allocator).
+pub struct BumpAllocator<R = SyscallRegion> {
+    region: R,
+    policy: OomPolicy,
 }
 
-impl BumpAllocator {
-    /// Create a new allocator with default heap configuration
+impl<const START: usize, const FALLBACK: usize> BumpAllocator<SyscallRegion<START, FALLBACK>> {
+    /// Create a new allocator for the heap granted by the VM
     pub const fn new() -> Self {
+        Self::with_region(SyscallRegion::new())
+    }
+}
+
+impl<R: HeapRegion> BumpAllocator<R> {
+    /// Create a new allocator managing `region`
+    pub const fn with_region(region: R) -> Self {
         Self {
-            start: HEAP_START_ADDRESS,
-            len: HEAP_LENGTH,
+            region,
+            policy: OomPolicy::Null,
         }
     }
 
+    /// Handle failed requests with `policy` instead of returning null
+    pub const fn with_oom_policy(mut self, policy: OomPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     /// Get heap usage statistics
     ///
     /// Returns (used_bytes, remaining_bytes)
-    pub fn usage() -> (usize, usize) {
+    pub fn usage(&self) -> (usize, usize) {
         unsafe {
-            let allocator = Self::new();
-            let pos_ptr = allocator.start as *mut usize;
-            let pos = *pos_ptr;
+            if !(*self.heap()).is_owned_by(BACKEND) {
+                // Not initialized yet; heaps too small for the allocator
+                // state have no usable space
+                return (
+                    0,
+                    self.heap_end(heap_size(&self.region))
+                        .saturating_sub(self.base()),
+                );
+            }
+            let header = &*self.header();
+            let pos = header.pos;
 
-            if pos == 0 {
-                // Not initialized yet
-                (0, allocator.len - size_of::<usize>())
+            if cfg!(feature = "bump-upward") {
+                // Position moves from bottom to top
+                let used = pos - self.base();
+                let remaining = header.end - pos;
+                (used, remaining)
             } else {
                 // Position moves from top to bottom
-                let heap_top = allocator.start + allocator.len;
-                let used = heap_top - pos;
-                let remaining = pos - (allocator.start + size_of::<usize>());
+                let used = header.end - pos;
+                let remaining = pos - self.base();
                 (used, remaining)
             }
         }
     }
+
+    /// Header describing the heap, once the first allocation initialized it
+    pub fn heap_header(&self) -> Option<HeapHeader> {
+        let heap = unsafe { &*self.heap() };
+        heap.is_owned_by(BACKEND).then_some(*heap)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        unsafe { (*self.heap()).last_failure_of(BACKEND) }
+    }
+
+    /// Allocation statistics since the heap was initialized
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        unsafe { self.stats_mut().as_ref().copied().unwrap_or_default() }
+    }
+
+    /// Capture the current position, to release everything allocated after
+    /// it with [`reset_to`](Self::reset_to)
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            pos: self.position(),
+        }
+    }
+
+    /// Release every allocation made since `checkpoint` was taken
+    ///
+    /// Does nothing if the position is already at or before the checkpoint.
+    ///
+    /// # Safety
+    ///
+    /// `checkpoint` must come from this allocator, and no block allocated
+    /// after it was taken may be used (or freed) afterwards.
+    pub unsafe fn reset_to(&self, checkpoint: Checkpoint) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+
+        let pos = match checkpoint.pos {
+            Some(pos) => pos,
+            // Taken before the first allocation
+            None if cfg!(feature = "bump-upward") => self.base(),
+            None => header.end,
+        };
+        let releases = if cfg!(feature = "bump-upward") {
+            pos >= self.base() && pos < header.pos
+        } else {
+            pos <= header.end && pos > header.pos
+        };
+        if releases {
+            header.pos = pos;
+        }
+    }
+
+    /// Run `f` with an [`Arena`], releasing the arena's allocations when it
+    /// returns
+    ///
+    /// The result cannot borrow from the arena. If anything else was left
+    /// allocated after an arena allocation (e.g. a `Vec` from the global
+    /// allocator that outlives the scope), nothing is released, so blocks
+    /// that may still be in use are never reclaimed.
+    pub fn scope<T>(&self, f: impl FnOnce(&Arena<'_, R>) -> T) -> T {
+        let checkpoint = self.checkpoint();
+        let arena = Arena {
+            allocator: self,
+            pos: Cell::new(checkpoint.pos),
+            shared: Cell::new(false),
+        };
+        let result = f(&arena);
+
+        if !arena.shared.get() && self.position() == arena.pos.get() {
+            // Only the arena allocated since the checkpoint
+            unsafe { self.reset_to(checkpoint) };
+        }
+        result
+    }
+
+    /// Current position, `None` until the heap is initialized
+    #[inline]
+    fn position(&self) -> Option<usize> {
+        unsafe {
+            (*self.heap())
+                .is_owned_by(BACKEND)
+                .then(|| (*self.header()).pos)
+        }
+    }
+
+    /// Heap header, which every region is large enough to hold
+    #[inline]
+    fn heap(&self) -> *mut HeapHeader {
+        self.region.start() as *mut HeapHeader
+    }
+
+    /// Allocator state, only valid once the heap header says this
+    /// allocator laid it out
+    #[inline]
+    fn header(&self) -> *mut Header {
+        self.region.start() as *mut Header
+    }
+
+    /// Set the position of a heap of `len` bytes to its starting value
+    fn init(&self, header: &mut Header, len: usize) {
+        let start = self.region.start();
+        header.end = self.heap_end(len);
+        header.pos = if cfg!(feature = "bump-upward") {
+            self.base()
+        } else {
+            header.end
+        };
+        header.fresh = header.pos;
+        header.heap.init(BACKEND, size_of::<Header>(), start, len);
+    }
+
+    /// Allocator state, laid out on first use
+    ///
+    /// Nothing past the heap header is read or written unless the region
+    /// can hold the whole state.
+    #[inline]
+    unsafe fn state(&self) -> Result<*mut Header, AllocFailure> {
+        if (*self.heap()).needs_init(BACKEND)? {
+            let len = heap_size(&self.region);
+            if len < size_of::<Header>() {
+                return Err(AllocFailure::SizeTooLarge);
+            }
+            // Statistics under a zeroed heap header mean it was wiped
+            #[cfg(feature = "stats")]
+            if (*self.header()).stats != AllocStats::default() {
+                return Err(AllocFailure::HeapCorrupted);
+            }
+            self.init(&mut *self.header(), len);
+        }
+        Ok(self.header())
+    }
+
+    /// Lay out the heap on first use and check `layout` against it
+    #[inline]
+    unsafe fn prepare(&self, layout: &Layout) -> Result<*mut Header, AllocFailure> {
+        let header = self.state()?;
+        check_layout(layout, (*header).end.saturating_sub(self.base()))?;
+        Ok(header)
+    }
+
+    /// Lowest usable address
+    #[inline]
+    fn base(&self) -> usize {
+        self.region.start() + size_of::<Header>()
+    }
+
+    /// Top of a heap of `len` bytes, rounded down to whole words
+    #[inline]
+    fn heap_end(&self, len: usize) -> usize {
+        (self.region.start() + len) & !(WORD - 1)
+    }
+
+    /// Boundary of the memory never handed out, the whole heap until the
+    /// heap is initialized
+    #[inline]
+    fn fresh(&self) -> usize {
+        if unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            unsafe { (*self.header()).fresh }
+        } else if cfg!(feature = "bump-upward") {
+            0
+        } else {
+            usize::MAX
+        }
+    }
+}
+
+/// Position of a [`BumpAllocator`], taken by
+/// [`checkpoint`](BumpAllocator::checkpoint)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// `None` if taken before the heap was initialized
+    pos: Option<usize>,
+}
+
+/// Allocations released together at the end of a
+/// [`BumpAllocator::scope`]
+///
+/// Values placed in the arena are never dropped.
+pub struct Arena<'a, R: HeapRegion = SyscallRegion> {
+    allocator: &'a BumpAllocator<R>,
+    /// Position after the arena's most recent allocation
+    pos: Cell<Option<usize>>,
+    /// Whether a block the arena does not own was left below one of its
+    /// allocations
+    shared: Cell<bool>,
+}
+
+// Arenas are only created by `scope`, whose closure accepts any `'a`, so
+// nothing borrowed for `'a` outlives the scope
+impl<'a, R: HeapRegion> Arena<'a, R> {
+    /// Allocate a block for `layout`, released when the scope ends
+    pub fn alloc_layout(&self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.allocator.position() != self.pos.get() {
+            // Something else allocated since the arena's last block
+            self.shared.set(true);
+        }
+        let ptr = unsafe { self.allocator.alloc(layout) };
+        self.pos.set(self.allocator.position());
+        NonNull::new(ptr)
+    }
+
+    /// Move `value` into the arena
+    pub fn alloc<T>(&self, value: T) -> Option<&'a mut T> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?.cast::<T>();
+        unsafe {
+            ptr.as_ptr().write(value);
+            Some(&mut *ptr.as_ptr())
+        }
+    }
 }
 
-impl Default for BumpAllocator {
+impl<R: HeapRegion + Default> Default for BumpAllocator<R> {
     fn default() -> Self {
-        Self::new()
+        Self::with_region(R::default())
     }
 }
 
-unsafe impl GlobalAlloc for BumpAllocator {
+impl<R: HeapRegion> Backend for BumpAllocator<R> {
+    #[cfg(not(feature = "bump-upward"))]
     #[inline]
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
         // Solana's bump allocator implementation
         // Source: agave/sdk/program/src/entrypoint.rs
 
-        let pos_ptr = self.start as *mut usize;
-        let mut pos = *pos_ptr;
+        let header = match self.prepare(&layout) {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        // Allocate from high to low (move position downward)
+        let pos = match header.pos.checked_sub(word_size(layout.size())) {
+            Some(pos) => pos & !(layout.align() - 1),
+            None => return fail(&mut header.heap, AllocFailure::OutOfMemory),
+        };
 
-        if pos == 0 {
-            // First allocation: start from heap top
-            pos = self.start + self.len;
+        // Check bounds
+        if pos < self.base() {
+            return fail(&mut header.heap, AllocFailure::OutOfMemory);
         }
 
-        // Allocate from high to low (move position downward)
-        pos = pos.saturating_sub(layout.size());
+        // Update position pointer
+        header.pos = pos;
+        header.fresh = header.fresh.min(pos);
+
+        pos as *mut u8
+    }
+
+    #[cfg(not(feature = "bump-upward"))]
+    #[inline]
+    unsafe fn allocate_zeroed(&self, layout: Layout) -> *mut u8 {
+        // Everything below the lowest position so far is still zero
+        let fresh = self.fresh();
+        let ptr = self.allocate(layout);
+        if !ptr.is_null() {
+            let end = ptr as usize + layout.size();
+            let dirty = (ptr as usize).max(fresh);
+            if end > dirty {
+                write_bytes(dirty as *mut u8, 0, end - dirty);
+            }
+        }
+        ptr
+    }
+
+    #[cfg(not(feature = "bump-upward"))]
+    #[inline]
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
 
-        // Align the position
-        pos &= !(layout.align().wrapping_sub(1));
+        // Only the most recent allocation can be given back; everything else
+        // is reclaimed when execution finishes
+        if ptr as usize == header.pos {
+            header.pos = ptr as usize + word_size(layout.size());
+        }
+    }
+
+    #[cfg(not(feature = "bump-upward"))]
+    #[inline]
+    unsafe fn reallocate(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let header = match self.prepare(&new_layout) {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        // Shrinking always happens in place. The freed tail lies above the
+        // block, where the position cannot reach it, so it stays allocated
+        if new_size <= layout.size() {
+            return ptr;
+        }
+
+        if ptr as usize != header.pos {
+            // Not the most recent allocation: move to a fresh block
+            let new_ptr = self.allocate(new_layout);
+            if !new_ptr.is_null() {
+                copy_nonoverlapping(ptr, new_ptr, layout.size());
+            }
+            return new_ptr;
+        }
+
+        // Most recent allocation: extend its block downward, so the old
+        // space is reused and a later dealloc still finds it. The block
+        // starts lower, so the contents are copied down (only `bump-upward`
+        // grows without copying)
+        let top = ptr as usize + word_size(layout.size());
+        let new_pos = match top.checked_sub(word_size(new_size)) {
+            Some(pos) => pos & !(layout.align() - 1),
+            None => return fail(&mut header.heap, AllocFailure::OutOfMemory),
+        };
+        if new_pos < self.base() {
+            return fail(&mut header.heap, AllocFailure::OutOfMemory);
+        }
+
+        copy(ptr, new_pos as *mut u8, layout.size());
+        header.pos = new_pos;
+        header.fresh = header.fresh.min(new_pos);
+
+        new_pos as *mut u8
+    }
+
+    #[cfg(feature = "bump-upward")]
+    #[inline]
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let header = match self.prepare(&layout) {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        // Align the position, then allocate from low to high
+        let align = layout.align();
+        let start = (header.pos + align - 1) & !(align - 1);
+        let end = start + word_size(layout.size());
 
         // Check bounds
-        if pos < self.start + size_of::<*mut u8>() {
-            return null_mut();  // Out of memory
+        if end > header.end {
+            return fail(&mut header.heap, AllocFailure::OutOfMemory);
         }
 
         // Update position pointer
-        *pos_ptr = pos;
+        header.pos = end;
+        header.fresh = header.fresh.max(end);
 
-        pos as *mut u8
+        start as *mut u8
+    }
+
+    #[cfg(feature = "bump-upward")]
+    #[inline]
+    unsafe fn allocate_zeroed(&self, layout: Layout) -> *mut u8 {
+        // Everything above the highest position so far is still zero
+        let fresh = self.fresh();
+        let ptr = self.allocate(layout);
+        if !ptr.is_null() {
+            let end = (ptr as usize + layout.size()).min(fresh);
+            if end > ptr as usize {
+                write_bytes(ptr, 0, end - ptr as usize);
+            }
+        }
+        ptr
     }
 
+    #[cfg(feature = "bump-upward")]
     #[inline]
-    unsafe fn dealloc(&self, _: *mut u8, _: Layout) {
-        // Bump allocator never deallocates
-        // Contract memory is reclaimed when execution finishes
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+
+        // Only the most recent allocation can be given back; everything else
+        // is reclaimed when execution finishes
+        if ptr as usize + word_size(layout.size()) == header.pos {
+            header.pos = ptr as usize;
+        }
+    }
+
+    #[cfg(feature = "bump-upward")]
+    #[inline]
+    unsafe fn reallocate(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let header = match self.prepare(&new_layout) {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        // Most recent allocation: resize in place by moving the position
+        if ptr as usize + word_size(layout.size()) == header.pos {
+            let end = ptr as usize + word_size(new_size);
+            if end > header.end {
+                return fail(&mut header.heap, AllocFailure::OutOfMemory);
+            }
+            header.pos = end;
+            header.fresh = header.fresh.max(end);
+            return ptr;
+        }
+
+        // Shrinking always happens in place
+        if new_size <= layout.size() {
+            return ptr;
+        }
+
+        // Not the most recent allocation: move to a fresh block
+        let new_ptr = self.allocate(new_layout);
+        if !new_ptr.is_null() {
+            copy_nonoverlapping(ptr, new_ptr, layout.size());
+        }
+        new_ptr
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn used(&self) -> usize {
+        // Read from the header, so recording stats never queries the region
+        if !unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            return 0;
+        }
+        let header = unsafe { &*self.header() };
+        if cfg!(feature = "bump-upward") {
+            header.pos - self.base()
+        } else {
+            header.end - header.pos
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn stats_mut(&self) -> *mut AllocStats {
+        let header = self.header();
+        unsafe {
+            if (*self.heap()).is_owned_by(BACKEND) {
+                &mut (*header).stats
+            } else {
+                null_mut()
+            }
+        }
+    }
+
+    #[inline]
+    fn failure(&self) -> Option<AllocFailure> {
+        self.last_failure()
+    }
+
+    #[inline]
+    fn oom_policy(&self) -> OomPolicy {
+        self.policy
+    }
+}
+
+unsafe impl<R: HeapRegion> GlobalAlloc for BumpAllocator<R> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        backend::alloc(self, layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        backend::alloc_zeroed(self, layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        backend::dealloc(self, ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        backend::realloc(self, ptr, layout, new_size)
     }
 }
 
-/// Type alias for compatibility with existing code
-#[allow(dead_code)]
-pub type TosAllocator = BumpAllocator;
+/// Declare the global allocator with a custom heap configuration
+///
+/// Expands to a `#[global_allocator] static ALLOCATOR` of type
+/// [`BumpAllocator`]:
+///
+/// - `len = ..` pins the heap to that length over a [`FixedRegion`], never
+///   asking the VM
+/// - `fallback_len = ..` keeps the [`SyscallRegion`], which uses the length
+///   the VM reports through `tos_get_heap_region` and falls back to this one
+///   when the VM reports none
+///
+/// `start` defaults to the TAKO heap address; with no arguments the
+/// allocator is the default [`SyscallRegion`] one.
+///
+/// [`FixedRegion`]: crate::FixedRegion
+///
+/// ```rust,no_run
+/// tos_alloc::declare_allocator!(len = 64 * 1024);
+///
+/// fn main() {
+///     let v = vec![1, 2, 3];
+///     let (used, remaining) = ALLOCATOR.usage();
+/// }
+/// ```
+#[macro_export]
+macro_rules! declare_allocator {
+    () => {
+        $crate::declare_allocator!(fallback_len = $crate::DEFAULT_HEAP_SIZE);
+    };
+    (len = $len:expr $(,)?) => {
+        $crate::declare_allocator!(start = $crate::HEAP_START, len = $len);
+    };
+    (fallback_len = $len:expr $(,)?) => {
+        $crate::declare_allocator!(start = $crate::HEAP_START, fallback_len = $len);
+    };
+    (start = $start:expr, len = $len:expr $(,)?) => {
+        #[global_allocator]
+        static ALLOCATOR: $crate::BumpAllocator<$crate::FixedRegion<{ $start }, { $len }>> =
+            $crate::BumpAllocator::with_region($crate::FixedRegion::new());
+    };
+    (start = $start:expr, fallback_len = $len:expr $(,)?) => {
+        #[global_allocator]
+        static ALLOCATOR: $crate::BumpAllocator<$crate::SyscallRegion<{ $start }, { $len }>> =
+            $crate::BumpAllocator::new();
+    };
+}
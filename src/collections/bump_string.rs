@@ -0,0 +1,151 @@
+//! Growable string on the bump heap
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str;
+
+use super::BumpVec;
+use crate::bump::BumpAllocator;
+use crate::region::{HeapRegion, SyscallRegion};
+
+/// String whose buffer lives on a [`BumpAllocator`]
+///
+/// A [`BumpVec`] of UTF-8 bytes, growing the same way.
+pub struct BumpString<'a, R: HeapRegion = SyscallRegion> {
+    bytes: BumpVec<'a, u8, R>,
+}
+
+impl<'a, R: HeapRegion> BumpString<'a, R> {
+    /// Create an empty string; nothing is allocated until the first push
+    pub fn new_in(allocator: &'a BumpAllocator<R>) -> Self {
+        Self {
+            bytes: BumpVec::new_in(allocator),
+        }
+    }
+
+    /// Create an empty string with room for `capacity` bytes
+    pub fn with_capacity_in(capacity: usize, allocator: &'a BumpAllocator<R>) -> Self {
+        Self {
+            bytes: BumpVec::with_capacity_in(capacity, allocator),
+        }
+    }
+
+    /// Copy `src` into a new string
+    pub fn from_str_in(src: &str, allocator: &'a BumpAllocator<R>) -> Self {
+        let mut string = Self::with_capacity_in(src.len(), allocator);
+        string.push_str(src);
+        string
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Number of bytes the buffer holds without growing
+    pub fn capacity(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    /// The contents as a `str`
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    /// The contents as a mutable `str`
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.bytes) }
+    }
+
+    /// Make room for at least `additional` more bytes
+    pub fn reserve(&mut self, additional: usize) {
+        self.bytes.reserve(additional);
+    }
+
+    /// Append `ch`
+    pub fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Append `src`
+    pub fn push_str(&mut self, src: &str) {
+        self.bytes.extend_from_slice(src.as_bytes());
+    }
+
+    /// Remove the last character
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.as_str().chars().next_back()?;
+        self.bytes.truncate(self.len() - ch.len_utf8());
+        Some(ch)
+    }
+
+    /// Empty the string, keeping the buffer
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    /// Shrink the buffer to the contents
+    pub fn shrink_to_fit(&mut self) {
+        self.bytes.shrink_to_fit();
+    }
+
+    /// Keep the contents for as long as the allocator is borrowed
+    pub fn into_bump_str(self) -> &'a mut str {
+        unsafe { str::from_utf8_unchecked_mut(self.bytes.into_bump_slice()) }
+    }
+}
+
+impl<R: HeapRegion> Deref for BumpString<'_, R> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<R: HeapRegion> DerefMut for BumpString<'_, R> {
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl<R: HeapRegion> fmt::Write for BumpString<'_, R> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.push(c);
+        Ok(())
+    }
+}
+
+impl<R: HeapRegion> fmt::Display for BumpString<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<R: HeapRegion> fmt::Debug for BumpString<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<R: HeapRegion> PartialEq<str> for BumpString<'_, R> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<R: HeapRegion> PartialEq<&str> for BumpString<'_, R> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
@@ -0,0 +1,273 @@
+//! Growable vector on the bump heap
+
+use alloc::alloc::handle_alloc_error;
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::mem::{self, ManuallyDrop};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::bump::BumpAllocator;
+use crate::region::{HeapRegion, SyscallRegion};
+
+/// Capacity of the first buffer of a vector
+const MIN_CAPACITY: usize = 4;
+
+/// Vector whose buffer lives on a [`BumpAllocator`]
+///
+/// Growing goes through the allocator's `realloc`, which extends the most
+/// recent allocation instead of abandoning it, so a vector built without
+/// other allocations in between uses about its capacity in heap. Only with
+/// `bump-upward` does the buffer stay where it is; the default downward
+/// layout extends it below its start and copies the values on every growth.
+/// Dropping the vector drops its values and frees the buffer, which gives
+/// the space back while it is still the most recent allocation.
+pub struct BumpVec<'a, T, R: HeapRegion = SyscallRegion> {
+    allocator: &'a BumpAllocator<R>,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+}
+
+impl<'a, T, R: HeapRegion> BumpVec<'a, T, R> {
+    /// Create an empty vector; nothing is allocated until the first push
+    pub fn new_in(allocator: &'a BumpAllocator<R>) -> Self {
+        Self {
+            allocator,
+            ptr: NonNull::dangling(),
+            len: 0,
+            // Zero-sized values never need a buffer
+            cap: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Create an empty vector with room for `capacity` values
+    pub fn with_capacity_in(capacity: usize, allocator: &'a BumpAllocator<R>) -> Self {
+        let mut vec = Self::new_in(allocator);
+        if capacity > vec.cap {
+            vec.resize_buffer(capacity);
+        }
+        vec
+    }
+
+    /// Number of values in the vector
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the vector holds no values
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of values the buffer holds without growing
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// The values as a slice
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The values as a mutable slice
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Make room for at least `additional` more values
+    pub fn reserve(&mut self, additional: usize) {
+        if self.cap - self.len >= additional {
+            return;
+        }
+        let required = self
+            .len
+            .checked_add(additional)
+            .unwrap_or_else(|| capacity_overflow());
+        self.resize_buffer(required.max(self.cap * 2).max(MIN_CAPACITY));
+    }
+
+    /// Append `value`
+    pub fn push(&mut self, value: T) {
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    /// Remove the last value
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    /// Append clones of the values in `other`
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(other.len());
+        for value in other {
+            // Counted one at a time, so a panicking `clone` leaks nothing
+            unsafe { self.ptr.as_ptr().add(self.len).write(value.clone()) };
+            self.len += 1;
+        }
+    }
+
+    /// Drop the values past the first `len`
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail =
+            ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(len) }, self.len - len);
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    /// Drop every value, keeping the buffer
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Shrink the buffer to the values it holds
+    ///
+    /// With `bump-upward`, if the buffer is the most recent allocation, the
+    /// spare capacity goes back to the heap. Growing downward the buffer
+    /// shrinks in place and its spare capacity stays allocated.
+    pub fn shrink_to_fit(&mut self) {
+        if self.cap == self.len || mem::size_of::<T>() == 0 {
+            return;
+        }
+        if self.len == 0 {
+            self.free_buffer();
+            self.ptr = NonNull::dangling();
+            self.cap = 0;
+        } else {
+            self.resize_buffer(self.len);
+        }
+    }
+
+    /// Keep the values for as long as the allocator is borrowed
+    ///
+    /// The buffer is shrunk first, as by [`shrink_to_fit`](Self::shrink_to_fit),
+    /// and the values are never dropped.
+    pub fn into_bump_slice(self) -> &'a mut [T] {
+        let mut vec = ManuallyDrop::new(self);
+        vec.shrink_to_fit();
+        unsafe { slice::from_raw_parts_mut(vec.ptr.as_ptr(), vec.len) }
+    }
+
+    /// Layout of the current buffer
+    #[inline]
+    fn buffer_layout(&self) -> Layout {
+        unsafe {
+            Layout::from_size_align_unchecked(self.cap * mem::size_of::<T>(), mem::align_of::<T>())
+        }
+    }
+
+    /// Move the values to a buffer of `cap` values through `realloc`, which
+    /// keeps the buffer in place when shrinking, and when growing the most
+    /// recent allocation with `bump-upward`
+    fn resize_buffer(&mut self, cap: usize) {
+        let layout = Layout::array::<T>(cap).unwrap_or_else(|_| capacity_overflow());
+        let ptr = unsafe {
+            if self.cap == 0 {
+                self.allocator.alloc(layout)
+            } else {
+                self.allocator.realloc(
+                    self.ptr.as_ptr().cast(),
+                    self.buffer_layout(),
+                    layout.size(),
+                )
+            }
+        };
+        self.ptr = NonNull::new(ptr.cast()).unwrap_or_else(|| handle_alloc_error(layout));
+        self.cap = cap;
+    }
+
+    /// Give the buffer back to the allocator
+    fn free_buffer(&mut self) {
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            unsafe {
+                self.allocator
+                    .dealloc(self.ptr.as_ptr().cast(), self.buffer_layout())
+            };
+        }
+    }
+}
+
+#[cold]
+fn capacity_overflow() -> ! {
+    panic!("capacity overflow")
+}
+
+impl<T, R: HeapRegion> Drop for BumpVec<'_, T, R> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+        self.free_buffer();
+    }
+}
+
+impl<T, R: HeapRegion> Deref for BumpVec<'_, T, R> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, R: HeapRegion> DerefMut for BumpVec<'_, T, R> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, R: HeapRegion> Extend<T> for BumpVec<'_, T, R> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'b, T, R: HeapRegion> IntoIterator for &'b BumpVec<'_, T, R> {
+    type Item = &'b T;
+    type IntoIter = slice::Iter<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'b, T, R: HeapRegion> IntoIterator for &'b mut BumpVec<'_, T, R> {
+    type Item = &'b mut T;
+    type IntoIter = slice::IterMut<'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: fmt::Debug, R: HeapRegion> fmt::Debug for BumpVec<'_, T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<T: PartialEq, R: HeapRegion> PartialEq<[T]> for BumpVec<'_, T, R> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.as_slice() == other
+    }
+}
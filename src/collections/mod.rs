@@ -0,0 +1,43 @@
+//! Heap-efficient collections
+//!
+//! Node-based collections such as `BTreeMap` make an allocation per node,
+//! and on a bump heap every node that is freed or moved stays lost until
+//! the contract ends. These types keep each collection in a single block:
+//!
+//! - [`BumpVec`] and [`BumpString`] live on a [`BumpAllocator`] borrowed
+//!   from the caller and extend their buffer instead of abandoning it while
+//!   it is the allocator's most recent allocation. With `bump-upward` the
+//!   buffer grows in place; growing downward (the default) it moves down
+//!   and the values are copied
+//! - [`VecMap`] and [`VecSet`] are sorted vectors with the `BTreeMap` and
+//!   `BTreeSet` API, in the global allocator, with any backend
+//!
+//! ```ignore
+//! use tos_alloc::collections::{BumpString, BumpVec, VecMap};
+//!
+//! static HEAP: BumpAllocator = BumpAllocator::new();
+//!
+//! let mut amounts = BumpVec::new_in(&HEAP);
+//! amounts.extend_from_slice(&[100u64, 200]);
+//! let mut memo = BumpString::new_in(&HEAP);
+//! write!(memo, "{} transfers", amounts.len()).unwrap();
+//!
+//! let mut balances = VecMap::new();
+//! balances.insert(account, 100u64);
+//! ```
+//!
+//! [`BumpAllocator`]: crate::BumpAllocator
+
+#[cfg(feature = "bump")]
+mod bump_string;
+#[cfg(feature = "bump")]
+mod bump_vec;
+mod vec_map;
+mod vec_set;
+
+#[cfg(feature = "bump")]
+pub use bump_string::BumpString;
+#[cfg(feature = "bump")]
+pub use bump_vec::BumpVec;
+pub use vec_map::{Iter, IterMut, VecMap};
+pub use vec_set::VecSet;
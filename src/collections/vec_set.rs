@@ -0,0 +1,198 @@
+//! Set stored as a sorted vector
+
+use alloc::vec::{self, Vec};
+use core::borrow::Borrow;
+use core::fmt;
+use core::slice;
+
+/// Ordered set with the `BTreeSet` API, stored as a sorted vector
+///
+/// Stored like [`VecMap`](super::VecMap): one block, binary-search lookups.
+#[derive(Clone, PartialEq, Eq)]
+pub struct VecSet<T> {
+    items: Vec<T>,
+}
+
+impl<T> VecSet<T> {
+    /// Create an empty set; nothing is allocated until the first insert
+    pub const fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    /// Create an empty set with room for `capacity` values
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Number of values
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Whether the set holds no values
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Remove every value, keeping the buffer
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// The values, sorted
+    pub fn as_slice(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Iterate over the values in order
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Smallest value
+    pub fn first(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    /// Largest value
+    pub fn last(&self) -> Option<&T> {
+        self.items.last()
+    }
+
+    /// Remove the smallest value
+    pub fn pop_first(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+        Some(self.items.remove(0))
+    }
+
+    /// Remove the largest value
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.items.pop()
+    }
+
+    /// Keep only the values for which `f` returns `true`
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.items.retain(f);
+    }
+}
+
+impl<T: Ord> VecSet<T> {
+    /// Index of `value`, or where it would be inserted
+    #[inline]
+    fn search<Q>(&self, value: &Q) -> Result<usize, usize>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.items
+            .binary_search_by(|probe| probe.borrow().cmp(value))
+    }
+
+    /// Whether the set holds `value`
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(value).is_ok()
+    }
+
+    /// Stored value equal to `value`
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Some(&self.items[self.search(value).ok()?])
+    }
+
+    /// Add `value`; returns `false`, keeping the stored value, if it was
+    /// already present
+    pub fn insert(&mut self, value: T) -> bool {
+        match self.search(&value) {
+            Ok(_) => false,
+            Err(index) => {
+                self.items.insert(index, value);
+                true
+            }
+        }
+    }
+
+    /// Remove `value`; returns whether it was present
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.take(value).is_some()
+    }
+
+    /// Remove and return the stored value equal to `value`
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self.search(value).ok()?;
+        Some(self.items.remove(index))
+    }
+
+    /// Restore the order after values were appended unsorted; of equal
+    /// values the first is kept
+    fn normalize(&mut self) {
+        self.items.sort();
+        self.items.dedup_by(|later, earlier| later == earlier);
+    }
+}
+
+impl<T> Default for VecSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for VecSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for VecSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self {
+            items: iter.into_iter().collect(),
+        };
+        set.normalize();
+        set
+    }
+}
+
+impl<T: Ord> Extend<T> for VecSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        // The sort is stable, so stored values win over new ones
+        self.items.extend(iter);
+        self.normalize();
+    }
+}
+
+impl<T> IntoIterator for VecSet<T> {
+    type Item = T;
+    type IntoIter = vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a VecSet<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
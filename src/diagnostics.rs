@@ -0,0 +1,88 @@
+//! Out-of-memory diagnostics
+//!
+//! With the `oom-diagnostics` feature, every allocation that returns null is
+//! logged before the caller's alloc error path runs, so failures can be read
+//! from transaction logs:
+//!
+//! ```text
+//! OOM_LOG_TAG | reason, size, align, used, peak
+//! ```
+//!
+//! `reason` is the [`AllocFailure`] code (0 if unknown), `size` and `align`
+//! describe the failing `Layout`, and `used` and `peak` are the bytes in use
+//! when it failed and at most so far. The failing `Layout` is also kept in
+//! [`AllocStats`](crate::AllocStats).
+//!
+//! The crate binds no log syscall itself. In the TAKO VM the contract hands
+//! it `tako_sdk::log_u64` with [`oom_log!`](crate::oom_log), so reports go
+//! through exactly the syscall the SDK uses.
+
+use core::alloc::Layout;
+
+use crate::failure::AllocFailure;
+
+#[cfg(target_os = "tos")]
+extern "Rust" {
+    /// Logs five values to the transaction log, defined by `oom_log!`
+    fn tos_alloc_log_u64(arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64);
+}
+
+/// First logged value of an OOM report ("OOM" in the top bytes), combined
+/// with the `AllocFailure` code
+pub const OOM_LOG_TAG: u64 = 0x4f4f_4d00_0000_0000;
+
+/// Values logged for a failed request, in the order shown above
+///
+/// Host tools can use this to recognise a report in transaction logs.
+pub fn oom_report(
+    failure: Option<AllocFailure>,
+    layout: &Layout,
+    used: usize,
+    peak: u64,
+) -> [u64; 5] {
+    [
+        OOM_LOG_TAG | failure.map_or(0, |failure| failure as u64),
+        layout.size() as u64,
+        layout.align() as u64,
+        used as u64,
+        peak,
+    ]
+}
+
+/// Log a failed request
+#[cold]
+#[inline(never)]
+pub(crate) fn report(failure: Option<AllocFailure>, layout: &Layout, used: usize, peak: u64) {
+    let values = oom_report(failure, layout, used, peak);
+
+    #[cfg(target_os = "tos")]
+    unsafe {
+        let [tag, size, align, used, peak] = values;
+        tos_alloc_log_u64(tag, size, align, used, peak);
+    }
+
+    #[cfg(not(target_os = "tos"))]
+    let _ = values;
+}
+
+/// Name the function that logs OOM reports, usually `tako_sdk::log_u64`
+///
+/// Invoke it once in the contract crate. It defines the hook the allocator
+/// calls on every failed request; building for the TAKO VM with
+/// `oom-diagnostics` and no `oom_log!` fails to link.
+///
+/// ```rust,no_run
+/// # mod tako_sdk {
+/// #     pub fn log_u64(_: u64, _: u64, _: u64, _: u64, _: u64) {}
+/// # }
+/// tos_alloc::oom_log!(tako_sdk::log_u64);
+/// ```
+#[macro_export]
+macro_rules! oom_log {
+    ($log:path) => {
+        #[no_mangle]
+        fn tos_alloc_log_u64(arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64) {
+            $log(arg1, arg2, arg3, arg4, arg5)
+        }
+    };
+}
@@ -0,0 +1,67 @@
+//! Allocation failure reasons
+//!
+//! Every backend validates a request against its heap before searching for
+//! space, so any `Layout` has a well-defined outcome. When a request fails,
+//! the reason is recorded in the heap header and reported by the
+//! allocator's `last_failure()`. A heap the allocator cannot use is never
+//! written to; `last_failure()` reports it from the header as found.
+
+use core::alloc::Layout;
+use core::ptr::null_mut;
+
+use crate::header::HeapHeader;
+
+/// Why an allocation returned null
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AllocFailure {
+    /// The request fits the heap, but not the space currently free
+    OutOfMemory = 1,
+    /// The requested size exceeds the whole usable heap
+    SizeTooLarge = 2,
+    /// The requested alignment exceeds the whole usable heap, or is more
+    /// than the backend can provide
+    AlignTooLarge = 3,
+    /// The heap header was overwritten, or another allocator manages the
+    /// heap; nothing is allocated from it any more
+    HeapCorrupted = 4,
+}
+
+impl AllocFailure {
+    /// Decode a reason stored in a heap header, 0 meaning none
+    #[inline]
+    pub(crate) fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            1 => Some(Self::OutOfMemory),
+            2 => Some(Self::SizeTooLarge),
+            3 => Some(Self::AlignTooLarge),
+            4 => Some(Self::HeapCorrupted),
+            _ => None,
+        }
+    }
+}
+
+/// Check that `layout` can ever be served from `usable` bytes of heap
+#[inline]
+pub(crate) fn check_layout(layout: &Layout, usable: usize) -> Result<(), AllocFailure> {
+    if layout.align() > usable {
+        return Err(AllocFailure::AlignTooLarge);
+    }
+    if layout.size() > usable {
+        return Err(AllocFailure::SizeTooLarge);
+    }
+    Ok(())
+}
+
+/// Record `failure` in the heap header and return null
+///
+/// `HeapCorrupted` is not recorded: the header belongs to another allocator
+/// or holds garbage, so it is left as it was.
+#[cold]
+#[inline(never)]
+pub(crate) fn fail(heap: &mut HeapHeader, failure: AllocFailure) -> *mut u8 {
+    if failure != AllocFailure::HeapCorrupted {
+        heap.failure = failure as u32;
+    }
+    null_mut()
+}
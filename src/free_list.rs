@@ -0,0 +1,384 @@
+//! Free-list allocator for TOS TAKO VM
+//!
+//! A first-fit allocator that returns memory to an address-ordered free list
+//! on `dealloc` and coalesces neighbouring free blocks, so contracts that
+//! repeatedly build and drop temporary collections do not exhaust the heap.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+#[cfg(feature = "stats")]
+use core::ptr::null_mut;
+
+use crate::backend::{self, Backend};
+use crate::failure::{check_layout, fail, AllocFailure};
+use crate::header::{BackendId, HeapHeader};
+use crate::oom::OomPolicy;
+use crate::region::{heap_size, HeapRegion, SyscallRegion};
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+const BACKEND: BackendId = BackendId::FreeList;
+
+/// Allocator state stored at the start of the heap
+#[repr(C)]
+struct Header {
+    heap: HeapHeader,
+    #[cfg(feature = "stats")]
+    stats: AllocStats,
+    /// Address of the first free block, 0 when the list is empty
+    free: usize,
+    /// Bytes currently handed out (rounded up to whole blocks)
+    used: usize,
+    /// Address of the first usable block
+    base: usize,
+    /// Heap top, discovered on initialization
+    end: usize,
+}
+
+/// Free block, stored in the freed memory itself
+#[repr(C)]
+struct FreeBlock {
+    /// Size of this block in bytes (a multiple of `BLOCK_SIZE`)
+    size: usize,
+    /// Address of the next free block, 0 at the end of the list
+    next: usize,
+}
+
+/// Allocation granularity; every block can hold a `FreeBlock` once freed
+const BLOCK_SIZE: usize = size_of::<FreeBlock>();
+
+#[inline]
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+/// Heap-based free-list allocator
+///
+/// **Key design decisions**:
+/// 1. **State on the heap** - The free-list head and usage counter live at
+///    heap start, like `BumpAllocator`'s position pointer. The heap size
+///    granted by the VM is discovered on first use
+/// 2. **No per-block headers** - `dealloc` receives the `Layout`, so block
+///    sizes are recomputed instead of stored
+/// 3. **Address-ordered free list** - Freed blocks are merged with adjacent
+///    free neighbours, keeping fragmentation low
+///
+/// # Heap Layout
+///
+/// ```text
+/// 0x300000000: HeapHeader (magic, backend, VM heap region)
+/// 0x300000020: Free-list head, used bytes, base, heap top
+/// 0x300000040: First block (initially one free block spanning the heap)
+/// ...
+/// 0x300008000: Heap top
+/// ```
+///
+/// # Usage
+///
+/// Enable the `free-list` feature (and disable the default `bump` feature):
+///
+/// ```toml
+/// tos-alloc = { path = "../../tos-alloc", default-features = false, features = ["free-list"] }
+/// ```
+///
+/// `TosAllocator` then refers to this allocator, so contract code is unchanged.
+pub struct FreeListAllocator<R = SyscallRegion> {
+    region: R,
+    policy: OomPolicy,
+}
+
+impl<const START: usize, const FALLBACK: usize> FreeListAllocator<SyscallRegion<START, FALLBACK>> {
+    /// Create a new allocator for the heap granted by the VM
+    pub const fn new() -> Self {
+        Self::with_region(SyscallRegion::new())
+    }
+}
+
+impl<R: HeapRegion> FreeListAllocator<R> {
+    /// Create a new allocator managing `region`
+    pub const fn with_region(region: R) -> Self {
+        Self {
+            region,
+            policy: OomPolicy::Null,
+        }
+    }
+
+    /// Handle failed requests with `policy` instead of returning null
+    pub const fn with_oom_policy(mut self, policy: OomPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get heap usage statistics
+    ///
+    /// Returns (used_bytes, remaining_bytes). Remaining bytes may be
+    /// fragmented across several free blocks.
+    pub fn usage(&self) -> (usize, usize) {
+        unsafe {
+            let (used, end) = if (*self.heap()).is_owned_by(BACKEND) {
+                let header = &*self.header();
+                (header.used, header.end)
+            } else {
+                (0, self.heap_end(heap_size(&self.region)))
+            };
+            // Heaps too small for the allocator state have no usable space
+            let usable = end.saturating_sub(self.base());
+            (used, usable - used)
+        }
+    }
+
+    /// Header describing the heap, once the first allocation initialized it
+    pub fn heap_header(&self) -> Option<HeapHeader> {
+        let heap = unsafe { &*self.heap() };
+        heap.is_owned_by(BACKEND).then_some(*heap)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        unsafe { (*self.heap()).last_failure_of(BACKEND) }
+    }
+
+    /// Allocation statistics since the heap was initialized
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        unsafe { self.stats_mut().as_ref().copied().unwrap_or_default() }
+    }
+
+    /// Heap header, which every region is large enough to hold
+    #[inline]
+    fn heap(&self) -> *mut HeapHeader {
+        self.region.start() as *mut HeapHeader
+    }
+
+    /// Allocator state, only valid once the heap header says this
+    /// allocator laid it out
+    #[inline]
+    fn header(&self) -> *mut Header {
+        self.region.start() as *mut Header
+    }
+
+    #[inline]
+    fn base(&self) -> usize {
+        (self.region.start() + size_of::<Header>() + BLOCK_SIZE - 1) & !(BLOCK_SIZE - 1)
+    }
+
+    /// Top of a heap of `len` bytes, rounded down to whole blocks
+    #[inline]
+    fn heap_end(&self, len: usize) -> usize {
+        (self.region.start() + len) & !(BLOCK_SIZE - 1)
+    }
+
+    /// Seed the free list with a single block spanning the heap
+    unsafe fn init(&self, header: &mut Header, len: usize) {
+        let start = self.region.start();
+        let base = self.base();
+        let end = self.heap_end(len);
+        header.base = base;
+        header.end = end;
+        header.used = 0;
+        if end > base {
+            let block = base as *mut FreeBlock;
+            (*block).size = end - base;
+            (*block).next = 0;
+            header.free = base;
+        } else {
+            header.free = 0;
+        }
+
+        header.heap.init(BACKEND, base - start, start, len);
+    }
+
+    /// Allocator state, laid out on first use
+    ///
+    /// Nothing past the heap header is read or written unless the region
+    /// can hold the whole state.
+    #[inline]
+    unsafe fn state(&self) -> Result<*mut Header, AllocFailure> {
+        if (*self.heap()).needs_init(BACKEND)? {
+            let len = heap_size(&self.region);
+            if len < size_of::<Header>() {
+                return Err(AllocFailure::SizeTooLarge);
+            }
+            // Statistics under a zeroed heap header mean it was wiped
+            #[cfg(feature = "stats")]
+            if (*self.header()).stats != AllocStats::default() {
+                return Err(AllocFailure::HeapCorrupted);
+            }
+            self.init(&mut *self.header(), len);
+        }
+        Ok(self.header())
+    }
+}
+
+impl<R: HeapRegion + Default> Default for FreeListAllocator<R> {
+    fn default() -> Self {
+        Self::with_region(R::default())
+    }
+}
+
+/// Round a request up to whole blocks
+#[inline]
+fn block_size(layout: &Layout) -> Option<usize> {
+    align_up(layout.size().max(1), BLOCK_SIZE)
+}
+
+impl<R: HeapRegion> Backend for FreeListAllocator<R> {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let header = match self.state() {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        if let Err(failure) = check_layout(&layout, header.end.saturating_sub(header.base)) {
+            return fail(&mut header.heap, failure);
+        }
+
+        let size = match block_size(&layout) {
+            Some(size) => size,
+            None => return fail(&mut header.heap, AllocFailure::SizeTooLarge),
+        };
+        let align = layout.align().max(BLOCK_SIZE);
+
+        // First fit: walk the list until a block can hold an aligned request
+        let mut prev: *mut usize = &mut header.free;
+        while *prev != 0 {
+            let addr = *prev;
+            let block = addr as *mut FreeBlock;
+            let block_size = (*block).size;
+            let next = (*block).next;
+
+            let aligned = match align_up(addr, align) {
+                Some(aligned) => aligned,
+                None => break,
+            };
+            let pad = aligned - addr;
+
+            if pad < block_size && size <= block_size - pad {
+                // Space after the allocation goes back on the list
+                let tail = block_size - pad - size;
+                let mut after = next;
+                if tail > 0 {
+                    let rest = (aligned + size) as *mut FreeBlock;
+                    (*rest).size = tail;
+                    (*rest).next = next;
+                    after = aligned + size;
+                }
+
+                // Space before it (alignment padding) keeps the block's slot
+                if pad > 0 {
+                    (*block).size = pad;
+                    (*block).next = after;
+                } else {
+                    *prev = after;
+                }
+
+                header.used += size;
+                return aligned as *mut u8;
+            }
+
+            prev = &mut (*block).next;
+        }
+
+        fail(&mut header.heap, AllocFailure::OutOfMemory)
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+        let addr = ptr as usize;
+        let size = match block_size(&layout) {
+            Some(size) => size,
+            None => return,
+        };
+
+        // Find the free blocks surrounding `addr`
+        let mut prev_addr = 0;
+        let mut link: *mut usize = &mut header.free;
+        while *link != 0 && *link < addr {
+            prev_addr = *link;
+            link = &mut (*(prev_addr as *mut FreeBlock)).next;
+        }
+        let next_addr = *link;
+
+        let block = addr as *mut FreeBlock;
+        (*block).size = size;
+        (*block).next = next_addr;
+        *link = addr;
+
+        // Merge with the following block
+        if next_addr != 0 && addr + size == next_addr {
+            let next = next_addr as *mut FreeBlock;
+            (*block).size += (*next).size;
+            (*block).next = (*next).next;
+        }
+
+        // Merge with the preceding block
+        if prev_addr != 0 {
+            let prev = prev_addr as *mut FreeBlock;
+            if prev_addr + (*prev).size == addr {
+                (*prev).size += (*block).size;
+                (*prev).next = (*block).next;
+            }
+        }
+
+        header.used -= size;
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn used(&self) -> usize {
+        // Read from the header, so recording stats never queries the region
+        if unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            unsafe { (*self.header()).used }
+        } else {
+            0
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn stats_mut(&self) -> *mut AllocStats {
+        let header = self.header();
+        unsafe {
+            if (*self.heap()).is_owned_by(BACKEND) {
+                &mut (*header).stats
+            } else {
+                null_mut()
+            }
+        }
+    }
+
+    #[inline]
+    fn failure(&self) -> Option<AllocFailure> {
+        self.last_failure()
+    }
+
+    #[inline]
+    fn oom_policy(&self) -> OomPolicy {
+        self.policy
+    }
+}
+
+unsafe impl<R: HeapRegion> GlobalAlloc for FreeListAllocator<R> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        backend::alloc(self, layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        backend::alloc_zeroed(self, layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        backend::dealloc(self, ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        backend::realloc(self, ptr, layout, new_size)
+    }
+}
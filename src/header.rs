@@ -0,0 +1,183 @@
+//! Common heap header
+//!
+//! Every backend starts its heap with a [`HeapHeader`] describing the heap,
+//! followed by its own state. The header lets an allocator tell a fresh
+//! (zeroed) heap from one it manages and from a corrupted one, and lets
+//! host tools parse heap dumps without knowing the backend in advance.
+//!
+//! ```text
+//! 0x300000000: HeapHeader (32 bytes, this layout is stable per version)
+//! 0x300000020: Backend state (header_len - 32 bytes)
+//! start + header_len: Memory handed out by the backend
+//! ```
+//!
+//! With the `stats` feature, an [`AllocStats`](crate::AllocStats) record
+//! (72 bytes) comes first in the backend state and `HEAP_FLAG_STATS` is set.
+
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+
+use crate::failure::AllocFailure;
+
+/// Marks a heap initialized by tos-alloc ("TOSA" in memory)
+pub const HEAP_MAGIC: u32 = u32::from_le_bytes(*b"TOSA");
+
+/// Version of the header layout, bumped on incompatible changes
+pub const HEAP_VERSION: u16 = 1;
+
+/// Set in `HeapHeader::flags` when `AllocStats` follow the header
+pub const HEAP_FLAG_STATS: u8 = 1;
+
+/// Backend managing a heap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BackendId {
+    Bump = 1,
+    BumpUpward = 2,
+    FreeList = 3,
+    Tlsf = 4,
+    Slab = 5,
+    Buddy = 6,
+}
+
+impl BackendId {
+    /// Decode a backend id stored in a heap header
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            1 => Some(Self::Bump),
+            2 => Some(Self::BumpUpward),
+            3 => Some(Self::FreeList),
+            4 => Some(Self::Tlsf),
+            5 => Some(Self::Slab),
+            6 => Some(Self::Buddy),
+            _ => None,
+        }
+    }
+}
+
+/// Header at the start of every heap
+///
+/// All zero until the first allocation. Fields have fixed widths, so the
+/// layout is the same on the TAKO VM and on 64-bit hosts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct HeapHeader {
+    /// `HEAP_MAGIC` once the heap is initialized
+    pub magic: u32,
+    /// `HEAP_VERSION` of the allocator that initialized the heap
+    pub version: u16,
+    /// `BackendId` of the allocator managing the heap
+    pub backend: u8,
+    /// `HEAP_FLAG_*` bits describing optional state after the header
+    pub flags: u8,
+    /// Bytes from heap start to the end of the allocator state
+    pub header_len: u32,
+    /// `AllocFailure` of the most recent failed request, 0 if none failed
+    pub failure: u32,
+    /// Heap start address
+    pub region_start: u64,
+    /// Heap length in bytes, after the `MAX_HEAP_SIZE` cap
+    pub region_len: u64,
+}
+
+impl HeapHeader {
+    /// Parse the header at the start of a heap dump
+    ///
+    /// Returns `None` if the heap is not initialized or was initialized by
+    /// an incompatible version.
+    pub fn read(heap: &[u8]) -> Option<Self> {
+        if heap.len() < size_of::<Self>() {
+            return None;
+        }
+        let header = unsafe { read_unaligned(heap.as_ptr() as *const Self) };
+        header.is_initialized().then_some(header)
+    }
+
+    /// Backend managing the heap
+    pub fn backend(&self) -> Option<BackendId> {
+        BackendId::from_raw(self.backend)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        AllocFailure::from_raw(self.failure)
+    }
+
+    #[inline]
+    fn is_initialized(&self) -> bool {
+        self.magic == HEAP_MAGIC && self.version == HEAP_VERSION
+    }
+
+    /// Whether `backend` must initialize the heap before using it
+    ///
+    /// Fails if the heap holds anything but a zeroed or a `backend` header,
+    /// i.e. the header was overwritten or another allocator owns the heap.
+    /// A zeroed magic alone is not enough, so wiping it does not re-initialize
+    /// a live heap.
+    #[inline]
+    pub(crate) fn needs_init(&self, backend: BackendId) -> Result<bool, AllocFailure> {
+        if self.is_initialized() && self.backend == backend as u8 {
+            Ok(false)
+        } else if self.is_blank() {
+            Ok(true)
+        } else {
+            Err(AllocFailure::HeapCorrupted)
+        }
+    }
+
+    /// Whether the header is still zeroed, apart from the failure code an
+    /// allocator records for a heap too small to initialize
+    #[inline]
+    fn is_blank(&self) -> bool {
+        let Self {
+            magic,
+            version,
+            backend,
+            flags,
+            header_len,
+            failure: _,
+            region_start,
+            region_len,
+        } = *self;
+        magic == 0
+            && version == 0
+            && backend == 0
+            && flags == 0
+            && header_len == 0
+            && region_start == 0
+            && region_len == 0
+    }
+
+    /// Why the most recent request to `backend` failed, `HeapCorrupted`
+    /// whenever `backend` cannot use the heap
+    #[inline]
+    pub(crate) fn last_failure_of(&self, backend: BackendId) -> Option<AllocFailure> {
+        match self.needs_init(backend) {
+            Ok(_) => self.last_failure(),
+            Err(failure) => Some(failure),
+        }
+    }
+
+    /// Whether `backend` manages the heap
+    #[inline]
+    pub(crate) fn is_owned_by(&self, backend: BackendId) -> bool {
+        self.is_initialized() && self.backend == backend as u8
+    }
+
+    /// Describe a heap `backend` has just laid out
+    #[inline]
+    pub(crate) fn init(&mut self, backend: BackendId, header_len: usize, start: usize, len: usize) {
+        self.version = HEAP_VERSION;
+        self.backend = backend as u8;
+        self.flags = if cfg!(feature = "stats") {
+            HEAP_FLAG_STATS
+        } else {
+            0
+        };
+        self.header_len = header_len as u32;
+        self.failure = 0;
+        self.region_start = start as u64;
+        self.region_len = len as u64;
+        self.magic = HEAP_MAGIC;
+    }
+}
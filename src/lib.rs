@@ -8,36 +8,143 @@
 //!
 //! # Usage
 //!
-//! ```no_run
+//! ```ignore
 //! #![no_std]
 //! #![no_main]
 //!
 //! extern crate alloc;
+//! use alloc::vec;
 //! use tos_alloc::TosAllocator;
 //!
 //! #[global_allocator]
-//! static ALLOCATOR: TosAllocator = TosAllocator;
+//! static ALLOCATOR: TosAllocator = TosAllocator::new();
 //!
 //! #[no_mangle]
 //! pub extern "C" fn entrypoint(input: *const u8) -> u64 {
-//!     // MUST initialize allocator first!
-//!     unsafe { TosAllocator::init(); }
-//!
 //!     // Now you can use Vec, BTreeMap, etc.
 //!     let v = vec![1, 2, 3];
 //!     0
 //! }
 //! ```
+//!
+//! # Backends
+//!
+//! `TosAllocator` refers to the backend selected by cargo feature. Exactly
+//! one backend feature may be enabled:
+//!
+//! - `bump` (default): [`BumpAllocator`], never reclaims memory
+//! - `free-list`: [`FreeListAllocator`], reclaims and coalesces freed blocks
+//! - `tlsf`: [`TlsfAllocator`], reclaims memory with O(1) alloc and dealloc
+//! - `slab`: [`SlabAllocator`], recycles small blocks through size classes
+//! - `buddy`: [`BuddyAllocator`], power-of-two blocks with cheap coalescing
+//!
+//! # Heap Regions
+//!
+//! Every backend is generic over the [`HeapRegion`] it manages, so the same
+//! allocator runs in the TAKO VM, in host tests and under other VM layouts:
+//!
+//! - [`SyscallRegion`] (default): heap reported by `tos_get_heap_region`
+//! - [`FixedRegion`]: heap pinned at a fixed address and length
+//! - [`LinkerRegion`]: heap between linker-script symbols
+//! - [`HostRegion`]: heap backed by a caller-provided buffer
+//! - [`HostHeap`]: zeroed heap owned by the host, for `cargo test`
+//!
+//! ```ignore
+//! use tos_alloc::{FixedRegion, TosAllocator};
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: TosAllocator<FixedRegion<0x300000000, { 64 * 1024 }>> =
+//!     TosAllocator::with_region(FixedRegion::new());
+//! ```
 
 #![no_std]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
 extern crate alloc;
 
+#[cfg(any(
+    all(
+        feature = "bump",
+        any(
+            feature = "free-list",
+            feature = "tlsf",
+            feature = "slab",
+            feature = "buddy"
+        )
+    ),
+    all(
+        feature = "free-list",
+        any(feature = "tlsf", feature = "slab", feature = "buddy")
+    ),
+    all(feature = "tlsf", any(feature = "slab", feature = "buddy")),
+    all(feature = "slab", feature = "buddy"),
+))]
+compile_error!(
+    "allocator backend features `bump`, `free-list`, `tlsf`, `slab` and `buddy` are mutually exclusive"
+);
+
+mod backend;
+#[cfg(feature = "buddy")]
+mod buddy;
+#[cfg(feature = "bump")]
 mod bump;
+pub mod collections;
 mod constants;
+#[cfg(feature = "oom-diagnostics")]
+mod diagnostics;
+mod failure;
+#[cfg(feature = "free-list")]
+mod free_list;
+mod header;
+mod local_arena;
+mod oom;
+mod pool;
+mod region;
+#[cfg(feature = "slab")]
+mod slab;
+mod stats;
+#[cfg(feature = "tlsf")]
+mod tlsf;
+#[cfg(feature = "bump")]
+mod typed;
 
-pub use bump::BumpAllocator;
+#[cfg(feature = "buddy")]
+pub use buddy::BuddyAllocator;
+#[cfg(feature = "bump")]
+pub use bump::{Arena, BumpAllocator, Checkpoint};
 pub use constants::*;
+#[cfg(feature = "oom-diagnostics")]
+pub use diagnostics::{oom_report, OOM_LOG_TAG};
+pub use failure::AllocFailure;
+#[cfg(feature = "free-list")]
+pub use free_list::FreeListAllocator;
+pub use header::{BackendId, HeapHeader, HEAP_FLAG_STATS, HEAP_MAGIC, HEAP_VERSION};
+pub use local_arena::LocalArena;
+pub use oom::{OomHandler, OomPolicy, OOM_PANIC_MESSAGE};
+pub use pool::{Pool, PoolBox};
+pub use region::{FixedRegion, HeapRegion, HostHeap, HostRegion, LinkerRegion, SyscallRegion};
+#[cfg(feature = "slab")]
+pub use slab::SlabAllocator;
+pub use stats::AllocStats;
+#[cfg(feature = "tlsf")]
+pub use tlsf::TlsfAllocator;
+
+/// Type alias for convenience
+#[cfg(feature = "bump")]
+pub type TosAllocator<R = SyscallRegion> = BumpAllocator<R>;
+
+/// Type alias for convenience
+#[cfg(feature = "free-list")]
+pub type TosAllocator<R = SyscallRegion> = FreeListAllocator<R>;
+
+/// Type alias for convenience
+#[cfg(feature = "tlsf")]
+pub type TosAllocator<R = SyscallRegion> = TlsfAllocator<R>;
+
+/// Type alias for convenience
+#[cfg(feature = "slab")]
+pub type TosAllocator<R = SyscallRegion> = SlabAllocator<R>;
 
 /// Type alias for convenience
-pub type TosAllocator = BumpAllocator;
+#[cfg(feature = "buddy")]
+pub type TosAllocator<R = SyscallRegion> = BuddyAllocator<R>;
@@ -0,0 +1,184 @@
+//! Local arenas carved out of the heap
+//!
+//! A [`LocalArena`] takes one chunk from the global allocator (the TOS heap
+//! in a contract) and bump-allocates short-lived data inside it. Dropping
+//! the arena gives the chunk back at once. With the `allocator-api` feature
+//! (nightly), `&LocalArena` is a `core::alloc::Allocator`, so collections
+//! can live in it:
+//!
+//! ```ignore
+//! let arena = LocalArena::new(4096).unwrap();
+//! let mut scratch = Vec::new_in(&arena);
+//! scratch.extend_from_slice(input);
+//! let boxed = Box::new_in(summary, &arena);
+//! ```
+
+use core::alloc::Layout;
+#[cfg(feature = "allocator-api")]
+use core::alloc::{AllocError, Allocator};
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use alloc::alloc::{alloc, dealloc};
+
+/// Alignment of the chunk; larger alignments are padded inside it
+const CHUNK_ALIGN: usize = 16;
+
+/// Bump arena over a chunk of the heap
+///
+/// Freeing the most recent allocation gives its space back; other blocks are
+/// released together by [`reset`](Self::reset) or when the arena is dropped.
+pub struct LocalArena {
+    chunk: NonNull<u8>,
+    capacity: usize,
+    /// Offset of the first free byte in the chunk
+    pos: Cell<usize>,
+}
+
+impl LocalArena {
+    /// Carve an arena of `capacity` bytes out of the global allocator
+    ///
+    /// Returns `None` if the heap cannot provide the chunk.
+    pub fn new(capacity: usize) -> Option<Self> {
+        let layout = Layout::from_size_align(capacity.max(1), CHUNK_ALIGN).ok()?;
+        let chunk = NonNull::new(unsafe { alloc(layout) })?;
+        Some(Self {
+            chunk,
+            capacity: layout.size(),
+            pos: Cell::new(0),
+        })
+    }
+
+    /// Size of the chunk in bytes
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes handed out, including alignment padding
+    pub fn used(&self) -> usize {
+        self.pos.get()
+    }
+
+    /// Allocate a block for `layout`, or `None` if the chunk is full
+    pub fn alloc_layout(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let base = self.chunk.as_ptr() as usize;
+        let start =
+            (base + self.pos.get()).checked_add(layout.align() - 1)? & !(layout.align() - 1);
+        let end = start.checked_add(layout.size())?;
+        if end > base + self.capacity {
+            return None;
+        }
+        self.pos.set(end - base);
+        NonNull::new(start as *mut u8)
+    }
+
+    /// Release every allocation at once
+    pub fn reset(&mut self) {
+        self.pos.set(0);
+    }
+
+    /// Whether `ptr..ptr + size` is the most recent allocation
+    #[cfg(feature = "allocator-api")]
+    #[inline]
+    fn is_last(&self, ptr: NonNull<u8>, size: usize) -> bool {
+        ptr.as_ptr() as usize + size == self.chunk.as_ptr() as usize + self.pos.get()
+    }
+
+    /// Free a block; only the most recent allocation gives its space back
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a block of `size` bytes allocated from this arena and
+    /// not used afterwards.
+    #[cfg(feature = "allocator-api")]
+    unsafe fn free(&self, ptr: NonNull<u8>, size: usize) {
+        if self.is_last(ptr, size) {
+            self.pos
+                .set(ptr.as_ptr() as usize - self.chunk.as_ptr() as usize);
+        }
+    }
+
+    /// Resize a block in place if it is the most recent allocation and the
+    /// chunk has room
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a block of `old_size` bytes allocated from this arena.
+    #[cfg(feature = "allocator-api")]
+    unsafe fn resize_in_place(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        if !self.is_last(ptr, old_size) {
+            return false;
+        }
+        let offset = ptr.as_ptr() as usize - self.chunk.as_ptr() as usize;
+        match offset.checked_add(new_size) {
+            Some(end) if end <= self.capacity => {
+                self.pos.set(end);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Drop for LocalArena {
+    fn drop(&mut self) {
+        unsafe {
+            let layout = Layout::from_size_align_unchecked(self.capacity, CHUNK_ALIGN);
+            dealloc(self.chunk.as_ptr(), layout);
+        }
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+unsafe impl Allocator for LocalArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = self.alloc_layout(layout).ok_or(AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.free(ptr, layout.size());
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if ptr.as_ptr() as usize & (new_layout.align() - 1) == 0
+            && self.resize_in_place(ptr, old_layout.size(), new_layout.size())
+        {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        new_ptr
+            .cast::<u8>()
+            .as_ptr()
+            .copy_from_nonoverlapping(ptr.as_ptr(), old_layout.size());
+        self.free(ptr, old_layout.size());
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        if ptr.as_ptr() as usize & (new_layout.align() - 1) == 0 {
+            // Returns the tail of the most recent allocation, else keeps it
+            self.resize_in_place(ptr, old_layout.size(), new_layout.size());
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        new_ptr
+            .cast::<u8>()
+            .as_ptr()
+            .copy_from_nonoverlapping(ptr.as_ptr(), new_layout.size());
+        self.free(ptr, old_layout.size());
+        Ok(new_ptr)
+    }
+}
@@ -0,0 +1,62 @@
+//! Out-of-memory policy
+//!
+//! What an allocator does when a request cannot be served is picked per
+//! allocator with `with_oom_policy`. By default it returns null, and Rust's
+//! alloc error path decides, which in a contract usually ends in the panic
+//! handler. [`OomPolicy::Abort`] panics with [`OOM_PANIC_MESSAGE`] instead,
+//! so an exhausted heap is told apart from any other failure.
+//!
+//! The crate binds no exit syscall of its own: the TAKO VM ends a contract
+//! through its panic handler, and contracts are built with `panic = "abort"`,
+//! so the panic never unwinds through the allocator.
+
+use core::alloc::Layout;
+
+use crate::failure::AllocFailure;
+
+/// Panic message of a contract aborted by [`OomPolicy::Abort`]
+pub const OOM_PANIC_MESSAGE: &str = "tos-alloc: out of memory";
+
+/// Called with a request that returned null and the reason; returning
+/// `true` retries the request
+pub type OomHandler = fn(Layout, AllocFailure) -> bool;
+
+/// What to do when an allocation or reallocation fails
+#[derive(Clone, Copy, Debug, Default)]
+pub enum OomPolicy {
+    /// Return null to the caller
+    #[default]
+    Null,
+    /// Panic with `OOM_PANIC_MESSAGE`; outside the TAKO VM, report through
+    /// `handle_alloc_error`
+    Abort,
+    /// Call the handler, which may free memory (e.g. drop caches) and ask
+    /// for a retry. It is called again after every failed retry, so it
+    /// must eventually return `false`, and it must not allocate from the
+    /// failing allocator
+    Handler(OomHandler),
+}
+
+impl OomPolicy {
+    /// Apply the policy to a failed request; `true` to retry it
+    #[cold]
+    pub(crate) fn handle(self, layout: Layout, failure: AllocFailure) -> bool {
+        match self {
+            OomPolicy::Null => false,
+            OomPolicy::Abort => abort(layout),
+            OomPolicy::Handler(handler) => handler(layout, failure),
+        }
+    }
+}
+
+#[cold]
+fn abort(layout: Layout) -> ! {
+    #[cfg(target_os = "tos")]
+    {
+        let _ = layout;
+        panic!("{}", OOM_PANIC_MESSAGE)
+    }
+
+    #[cfg(not(target_os = "tos"))]
+    alloc::alloc::handle_alloc_error(layout)
+}
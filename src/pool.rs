@@ -0,0 +1,231 @@
+//! Fixed-size object pools
+//!
+//! A [`Pool`] takes one chunk from the global allocator (the TOS heap in a
+//! contract) and splits it into slots for values of a single type. Freed
+//! slots are chained through an intrusive free list and handed out again,
+//! so a contract that keeps creating and dropping the same kind of record
+//! reuses memory even on the bump backend:
+//!
+//! ```ignore
+//! let orders = Pool::<Order>::new(64).unwrap();
+//! match orders.alloc(Order::new(price, amount)) {
+//!     Ok(order) => book.insert(order.id, order),
+//!     // The pool is full; the order comes back unharmed
+//!     Err(order) => reject(order),
+//! }
+//! ```
+
+use core::alloc::Layout;
+use core::cell::Cell;
+#[cfg(feature = "stats")]
+use core::cell::RefCell;
+use core::fmt;
+use core::mem::{size_of, ManuallyDrop};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, null_mut, NonNull};
+
+use alloc::alloc::{alloc, dealloc};
+
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+/// A slot holds a value while in use and the next free slot otherwise
+#[repr(C)]
+union Slot<T> {
+    value: ManuallyDrop<T>,
+    next: *mut Slot<T>,
+}
+
+/// Pool of `capacity` slots for values of type `T`
+///
+/// Values are handed out as [`PoolBox`]es, which give their slot back when
+/// dropped. The chunk is returned to the heap when the pool is dropped.
+pub struct Pool<T> {
+    slots: NonNull<Slot<T>>,
+    capacity: usize,
+    /// Head of the free list, null if empty
+    free: Cell<*mut Slot<T>>,
+    /// Index of the first slot never handed out; slots from here on are not
+    /// in the free list yet
+    fresh: Cell<usize>,
+    /// Slots in use
+    len: Cell<usize>,
+    #[cfg(feature = "stats")]
+    stats: RefCell<AllocStats>,
+}
+
+impl<T> Pool<T> {
+    /// Carve a pool of `capacity` slots out of the global allocator
+    ///
+    /// Returns `None` if `capacity` is 0 or the heap cannot provide the
+    /// chunk.
+    pub fn new(capacity: usize) -> Option<Self> {
+        if capacity == 0 {
+            return None;
+        }
+        let layout = Layout::array::<Slot<T>>(capacity).ok()?;
+        let slots = NonNull::new(unsafe { alloc(layout) })?.cast();
+        Some(Self {
+            slots,
+            capacity,
+            free: Cell::new(null_mut()),
+            fresh: Cell::new(0),
+            len: Cell::new(0),
+            #[cfg(feature = "stats")]
+            stats: RefCell::new(AllocStats::default()),
+        })
+    }
+
+    /// Number of slots
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of slots in use
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Whether no slot is in use
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// Bytes of slots in use and free, like the allocators' `usage()`
+    pub fn usage(&self) -> (usize, usize) {
+        let slot = size_of::<Slot<T>>();
+        (self.len() * slot, (self.capacity - self.len()) * slot)
+    }
+
+    /// Counters for the slots handed out and returned
+    ///
+    /// Sizes are in bytes, as for the allocators; `padding` is the slot
+    /// space values do not use.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        *self.stats.borrow()
+    }
+
+    /// Move `value` into a free slot
+    ///
+    /// If every slot is in use, `value` is handed back as the error.
+    pub fn alloc(&self, value: T) -> Result<PoolBox<'_, T>, T> {
+        #[cfg(feature = "stats")]
+        let (before, _) = self.usage();
+
+        let slot = self.take_slot();
+
+        #[cfg(feature = "stats")]
+        self.stats.borrow_mut().record_alloc(
+            slot.map_or(null_mut(), |slot| slot.as_ptr().cast()),
+            &Layout::new::<T>(),
+            before,
+            self.usage().0,
+        );
+
+        let Some(slot) = slot else {
+            return Err(value);
+        };
+        let slot = slot.cast::<T>();
+        unsafe { slot.as_ptr().write(value) };
+        Ok(PoolBox { pool: self, slot })
+    }
+
+    /// Take a slot from the free list, else the first fresh one
+    fn take_slot(&self) -> Option<NonNull<Slot<T>>> {
+        let slot = match NonNull::new(self.free.get()) {
+            Some(slot) => {
+                self.free.set(unsafe { (*slot.as_ptr()).next });
+                slot
+            }
+            None if self.fresh.get() < self.capacity => {
+                let index = self.fresh.get();
+                self.fresh.set(index + 1);
+                unsafe { NonNull::new_unchecked(self.slots.as_ptr().add(index)) }
+            }
+            None => return None,
+        };
+        self.len.set(self.len.get() + 1);
+        Some(slot)
+    }
+
+    /// Put a slot whose value was moved out or dropped on the free list
+    ///
+    /// # Safety
+    ///
+    /// `slot` must have been taken from this pool and not be used afterwards.
+    unsafe fn release(&self, slot: NonNull<T>) {
+        let slot = slot.cast::<Slot<T>>().as_ptr();
+        (*slot).next = self.free.get();
+        self.free.set(slot);
+        self.len.set(self.len.get() - 1);
+
+        #[cfg(feature = "stats")]
+        self.stats.borrow_mut().record_dealloc();
+    }
+}
+
+impl<T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let layout = Layout::array::<Slot<T>>(self.capacity).unwrap_unchecked();
+            dealloc(self.slots.as_ptr().cast(), layout);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Pool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// Value in a [`Pool`] slot; dropping it drops the value and frees the slot
+pub struct PoolBox<'a, T> {
+    pool: &'a Pool<T>,
+    slot: NonNull<T>,
+}
+
+impl<T> PoolBox<'_, T> {
+    /// Move the value out, freeing the slot
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        unsafe {
+            let value = this.slot.as_ptr().read();
+            this.pool.release(this.slot);
+            value
+        }
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.slot.as_ptr());
+            self.pool.release(self.slot);
+        }
+    }
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.slot.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.slot.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
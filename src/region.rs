@@ -0,0 +1,276 @@
+//! Heap region providers
+//!
+//! Allocator backends do not know where their heap lives; they ask a
+//! [`HeapRegion`]. The TAKO VM reports the heap granted to a contract through
+//! the `tos_get_heap_region` syscall ([`SyscallRegion`], the default), but the
+//! heap can also be pinned at a fixed address ([`FixedRegion`]), placed by the
+//! linker ([`LinkerRegion`]), or backed by a host buffer ([`HostRegion`],
+//! [`HostHeap`]) for tests.
+
+use alloc::alloc::{alloc_zeroed, dealloc, handle_alloc_error};
+use core::alloc::Layout;
+use core::mem::size_of;
+use core::ptr::addr_of;
+
+use crate::constants::{DEFAULT_HEAP_SIZE, HEAP_START, MAX_HEAP_SIZE};
+use crate::header::HeapHeader;
+
+#[cfg(target_os = "tos")]
+extern "C" {
+    /// Writes the heap start address and length, returns 0 on success
+    fn tos_get_heap_region(start: *mut u64, len: *mut u64) -> u64;
+}
+
+extern "C" {
+    /// First byte of the heap, defined by the contract's linker script
+    static __tos_heap_start: u8;
+    /// One past the last byte of the heap, defined by the linker script
+    static __tos_heap_end: u8;
+}
+
+/// Query the VM for the heap region granted to this contract
+///
+/// Returns (heap_start, heap_len), or `None` outside the TAKO VM or when
+/// the VM reports an empty heap.
+#[inline]
+pub fn vm_heap_region() -> Option<(usize, usize)> {
+    #[cfg(target_os = "tos")]
+    unsafe {
+        let mut start = 0u64;
+        let mut len = 0u64;
+        if tos_get_heap_region(&mut start, &mut len) == 0 && len != 0 {
+            return Some((start as usize, len as usize));
+        }
+    }
+
+    None
+}
+
+/// Memory an allocator manages
+///
+/// `start` is called on every allocation and must be cheap. `size` is called
+/// once, when the allocator lays out its state at heap start, so it may do
+/// real work such as a syscall; allocators keep the result in the heap
+/// header's `region_len`. Only `usage()` before the first allocation, when
+/// there is no header yet, queries it again.
+///
+/// # Safety
+///
+/// `start .. start + size` must be valid for reads and writes, zeroed before
+/// the first allocation, and used by nothing but the allocator. `start` must
+/// be 8-byte aligned, `size` at least `size_of::<HeapHeader>()` (32 bytes),
+/// and both values must never change. Allocators only touch the heap header
+/// of a region too small for their state, and every allocation then fails
+/// with `SizeTooLarge`.
+pub unsafe trait HeapRegion {
+    /// Heap start address
+    fn start(&self) -> usize;
+
+    /// Heap length in bytes
+    fn size(&self) -> usize;
+}
+
+unsafe impl<R: HeapRegion + ?Sized> HeapRegion for &R {
+    #[inline]
+    fn start(&self) -> usize {
+        (**self).start()
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        (**self).size()
+    }
+}
+
+/// Heap length an allocator may use, capped at `MAX_HEAP_SIZE`
+#[inline]
+pub(crate) fn heap_size<R: HeapRegion>(region: &R) -> usize {
+    region.size().min(MAX_HEAP_SIZE)
+}
+
+/// Heap region discovered through `tos_get_heap_region`
+///
+/// The VM-granted length wins when the VM reports a heap at `START`, so a VM
+/// configured with 64 KB or 256 KB is used in full. `FALLBACK` is the length
+/// assumed when the VM reports no heap there, e.g. on older VMs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyscallRegion<const START: usize = HEAP_START, const FALLBACK: usize = DEFAULT_HEAP_SIZE>;
+
+impl<const START: usize, const FALLBACK: usize> SyscallRegion<START, FALLBACK> {
+    /// Create a new region
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+unsafe impl<const START: usize, const FALLBACK: usize> HeapRegion
+    for SyscallRegion<START, FALLBACK>
+{
+    #[inline]
+    fn start(&self) -> usize {
+        START
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        match vm_heap_region() {
+            Some((vm_start, vm_len)) if vm_start == START => vm_len,
+            _ => FALLBACK,
+        }
+    }
+}
+
+/// Heap region pinned at `START` with length `LEN`, never queried from the VM
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedRegion<const START: usize = HEAP_START, const LEN: usize = DEFAULT_HEAP_SIZE>;
+
+impl<const START: usize, const LEN: usize> FixedRegion<START, LEN> {
+    /// Create a new region
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+unsafe impl<const START: usize, const LEN: usize> HeapRegion for FixedRegion<START, LEN> {
+    #[inline]
+    fn start(&self) -> usize {
+        START
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        LEN
+    }
+}
+
+/// Heap region between the `__tos_heap_start` and `__tos_heap_end` symbols
+///
+/// For VM layouts where the contract's linker script places the heap. The
+/// symbols must be defined when this region is used, or linking fails.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkerRegion;
+
+impl LinkerRegion {
+    /// Create a new region
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+unsafe impl HeapRegion for LinkerRegion {
+    #[inline]
+    fn start(&self) -> usize {
+        addr_of!(__tos_heap_start) as usize
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        addr_of!(__tos_heap_end) as usize - self.start()
+    }
+}
+
+/// Heap region backed by a caller-provided buffer
+///
+/// Lets host builds and tests run the allocators outside the TAKO VM.
+#[derive(Clone, Copy, Debug)]
+pub struct HostRegion {
+    start: usize,
+    size: usize,
+}
+
+impl HostRegion {
+    /// Create a region over `size` bytes at `start`
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be zeroed, 8-byte aligned, at least
+    /// `size_of::<HeapHeader>()` bytes long, and stay valid and unused by
+    /// anything else for as long as an allocator uses this region.
+    pub unsafe fn new(start: *mut u8, size: usize) -> Self {
+        Self {
+            start: start as usize,
+            size,
+        }
+    }
+}
+
+unsafe impl HeapRegion for HostRegion {
+    #[inline]
+    fn start(&self) -> usize {
+        self.start
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Zeroed heap owned by the host, for running the allocators in `cargo test`
+///
+/// The buffer comes from the host's global allocator and is aligned to the
+/// heap size rounded up to a power of two, like the TAKO heap, so any
+/// alignment a backend can serve is possible. An allocator can own the heap
+/// (`TosAllocator::with_region(HostHeap::new(4096))`) or borrow it, which
+/// keeps the heap inspectable from the test.
+#[derive(Debug)]
+pub struct HostHeap {
+    start: usize,
+    size: usize,
+}
+
+impl HostHeap {
+    /// Allocate a zeroed heap of `size` bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is less than `size_of::<HeapHeader>()`.
+    pub fn new(size: usize) -> Self {
+        assert!(
+            size >= size_of::<HeapHeader>(),
+            "host heap must hold the heap header"
+        );
+        let layout = Self::layout(size);
+        let start = unsafe { alloc_zeroed(layout) };
+        if start.is_null() {
+            handle_alloc_error(layout);
+        }
+        Self {
+            start: start as usize,
+            size,
+        }
+    }
+
+    /// The heap memory, e.g. to inspect allocator state at heap start
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.start as *const u8, self.size) }
+    }
+
+    /// The heap memory, mutably, e.g. to simulate stray writes
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.start as *mut u8, self.size) }
+    }
+
+    fn layout(size: usize) -> Layout {
+        let align = size.next_power_of_two().max(16);
+        Layout::from_size_align(size, align).expect("host heap too large")
+    }
+}
+
+impl Drop for HostHeap {
+    fn drop(&mut self) {
+        unsafe { dealloc(self.start as *mut u8, Self::layout(self.size)) };
+    }
+}
+
+unsafe impl HeapRegion for HostHeap {
+    #[inline]
+    fn start(&self) -> usize {
+        self.start
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.size
+    }
+}
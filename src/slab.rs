@@ -0,0 +1,358 @@
+//! Size-class slab allocator for TOS TAKO VM
+//!
+//! Small requests are rounded up to a power-of-two size class (8 to 512
+//! bytes) and recycled through per-class free lists, which suits contracts
+//! dominated by `BTreeMap` nodes and small `Box`es. Larger requests fall back
+//! to a bump region shared with fresh slab blocks.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use core::ptr::{null_mut, write_bytes};
+
+use crate::backend::{self, Backend};
+use crate::failure::{check_layout, fail, AllocFailure};
+use crate::header::{BackendId, HeapHeader};
+use crate::oom::OomPolicy;
+use crate::region::{heap_size, HeapRegion, SyscallRegion};
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+/// Smallest size class; a freed block must hold the next-free link
+const MIN_CLASS_SIZE: usize = size_of::<usize>();
+
+/// Largest size class; bigger requests come from the bump region
+const MAX_CLASS_SIZE: usize = 512;
+
+/// Number of size classes (8, 16, 32, 64, 128, 256, 512)
+const CLASS_COUNT: usize =
+    (MAX_CLASS_SIZE.trailing_zeros() - MIN_CLASS_SIZE.trailing_zeros() + 1) as usize;
+
+const BACKEND: BackendId = BackendId::Slab;
+
+/// Allocator state stored at the start of the heap
+#[repr(C)]
+struct Header {
+    heap: HeapHeader,
+    #[cfg(feature = "stats")]
+    stats: AllocStats,
+    /// Bump position, moving down from heap top
+    pos: usize,
+    /// Heap top, discovered on the first allocation
+    end: usize,
+    /// Bytes currently handed out (slab blocks count their full class size)
+    /// plus the alignment padding lost in the bump region
+    used: usize,
+    /// Free-list head per size class, 0 when empty
+    free: [usize; CLASS_COUNT],
+}
+
+/// Size class index and block size for a request, if it fits a class
+#[inline]
+fn size_class(layout: &Layout) -> Option<(usize, usize)> {
+    let size = layout.size().max(layout.align()).max(MIN_CLASS_SIZE);
+    if size > MAX_CLASS_SIZE {
+        return None;
+    }
+    let block = size.next_power_of_two();
+    let class = (block.trailing_zeros() - MIN_CLASS_SIZE.trailing_zeros()) as usize;
+    Some((class, block))
+}
+
+/// Heap-based size-class slab allocator
+///
+/// **Key design decisions**:
+/// 1. **State on the heap** - The bump position and per-class free-list
+///    heads live at heap start, extending `BumpAllocator`'s position pointer.
+///    The heap size granted by the VM is discovered on first use
+/// 2. **Power-of-two size classes** - Blocks are aligned to their size, so
+///    any request with `align <= size` fits its class
+/// 3. **Lazy carving** - A class takes one block at a time from the bump
+///    region, so unused classes cost nothing
+/// 4. **Large requests are never reclaimed** - Requests above 512 bytes are
+///    bump allocated, exactly like `BumpAllocator`
+///
+/// # Heap Layout
+///
+/// ```text
+/// 0x300000000: HeapHeader (magic, backend, VM heap region)
+/// 0x300000020: Bump position, heap top, used bytes, 7 free-list heads
+/// 0x300000070: Free space
+/// ...          Slab blocks and large allocations grow downward
+/// 0x300008000: Heap top (initial position value)
+/// ```
+///
+/// # Usage
+///
+/// Enable the `slab` feature (and disable the default `bump` feature):
+///
+/// ```toml
+/// tos-alloc = { path = "../../tos-alloc", default-features = false, features = ["slab"] }
+/// ```
+///
+/// `TosAllocator` then refers to this allocator, so contract code is unchanged.
+pub struct SlabAllocator<R = SyscallRegion> {
+    region: R,
+    policy: OomPolicy,
+}
+
+impl<const START: usize, const FALLBACK: usize> SlabAllocator<SyscallRegion<START, FALLBACK>> {
+    /// Create a new allocator for the heap granted by the VM
+    pub const fn new() -> Self {
+        Self::with_region(SyscallRegion::new())
+    }
+}
+
+impl<R: HeapRegion> SlabAllocator<R> {
+    /// Create a new allocator managing `region`
+    pub const fn with_region(region: R) -> Self {
+        Self {
+            region,
+            policy: OomPolicy::Null,
+        }
+    }
+
+    /// Handle failed requests with `policy` instead of returning null
+    pub const fn with_oom_policy(mut self, policy: OomPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get heap usage statistics
+    ///
+    /// Returns (used_bytes, remaining_bytes). Used bytes include alignment
+    /// padding lost in the bump region. Remaining bytes include freed slab
+    /// blocks, which only requests of the same size class can reuse.
+    pub fn usage(&self) -> (usize, usize) {
+        unsafe {
+            let (used, end) = if (*self.heap()).is_owned_by(BACKEND) {
+                let header = &*self.header();
+                (header.used, header.end)
+            } else {
+                (0, self.heap_end(heap_size(&self.region)))
+            };
+            // Heaps too small for the allocator state have no usable space
+            let usable = end.saturating_sub(self.base());
+            (used, usable - used)
+        }
+    }
+
+    /// Header describing the heap, once the first allocation initialized it
+    pub fn heap_header(&self) -> Option<HeapHeader> {
+        let heap = unsafe { &*self.heap() };
+        heap.is_owned_by(BACKEND).then_some(*heap)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        unsafe { (*self.heap()).last_failure_of(BACKEND) }
+    }
+
+    /// Allocation statistics since the heap was initialized
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        unsafe { self.stats_mut().as_ref().copied().unwrap_or_default() }
+    }
+
+    /// Heap header, which every region is large enough to hold
+    #[inline]
+    fn heap(&self) -> *mut HeapHeader {
+        self.region.start() as *mut HeapHeader
+    }
+
+    /// Allocator state, only valid once the heap header says this
+    /// allocator laid it out
+    #[inline]
+    fn header(&self) -> *mut Header {
+        self.region.start() as *mut Header
+    }
+
+    /// Lowest address the bump region may reach
+    #[inline]
+    fn base(&self) -> usize {
+        self.region.start() + size_of::<Header>()
+    }
+
+    /// Top of a heap of `len` bytes
+    #[inline]
+    fn heap_end(&self, len: usize) -> usize {
+        self.region.start() + len
+    }
+
+    /// Discover the heap and start the bump region at its top
+    fn init(&self, header: &mut Header, len: usize) {
+        let start = self.region.start();
+        header.end = self.heap_end(len);
+        header.pos = header.end;
+        header.heap.init(BACKEND, size_of::<Header>(), start, len);
+    }
+
+    /// Allocator state, laid out on first use
+    ///
+    /// Nothing past the heap header is read or written unless the region
+    /// can hold the whole state.
+    #[inline]
+    unsafe fn state(&self) -> Result<*mut Header, AllocFailure> {
+        if (*self.heap()).needs_init(BACKEND)? {
+            let len = heap_size(&self.region);
+            if len < size_of::<Header>() {
+                return Err(AllocFailure::SizeTooLarge);
+            }
+            // Statistics under a zeroed heap header mean it was wiped
+            #[cfg(feature = "stats")]
+            if (*self.header()).stats != AllocStats::default() {
+                return Err(AllocFailure::HeapCorrupted);
+            }
+            self.init(&mut *self.header(), len);
+        }
+        Ok(self.header())
+    }
+
+    /// Take `size` bytes aligned to `align` from the bump region
+    #[inline]
+    fn bump(&self, header: &mut Header, size: usize, align: usize) -> *mut u8 {
+        // Allocate from high to low (move position downward)
+        let pos = match header.pos.checked_sub(size) {
+            Some(pos) if pos & !(align - 1) >= self.base() => pos & !(align - 1),
+            _ => return null_mut(), // Out of memory
+        };
+
+        header.pos = pos;
+        pos as *mut u8
+    }
+}
+
+impl<R: HeapRegion + Default> Default for SlabAllocator<R> {
+    fn default() -> Self {
+        Self::with_region(R::default())
+    }
+}
+
+impl<R: HeapRegion> Backend for SlabAllocator<R> {
+    #[inline]
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let header = match self.state() {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        if let Err(failure) = check_layout(&layout, header.end.saturating_sub(self.base())) {
+            return fail(&mut header.heap, failure);
+        }
+
+        let pos = header.pos;
+        let ptr = match size_class(&layout) {
+            Some((class, block)) => {
+                let head = header.free[class];
+                if head != 0 {
+                    // Reuse a freed block of this class
+                    header.free[class] = *(head as *const usize);
+                    header.used += block;
+                    return head as *mut u8;
+                }
+                self.bump(header, block, block)
+            }
+            None => self.bump(header, layout.size(), layout.align()),
+        };
+
+        if ptr.is_null() {
+            return fail(&mut header.heap, AllocFailure::OutOfMemory);
+        }
+        // The alignment padding skipped in the bump region is lost, so it
+        // counts as used
+        header.used += pos - header.pos;
+        ptr
+    }
+
+    #[inline]
+    unsafe fn allocate_zeroed(&self, layout: Layout) -> *mut u8 {
+        // Memory below the bump position was never handed out, so it is
+        // still zero; freed blocks and large allocations lie above it
+        let fresh = if (*self.heap()).is_owned_by(BACKEND) {
+            (*self.header()).pos
+        } else {
+            usize::MAX
+        };
+
+        let ptr = self.allocate(layout);
+        if !ptr.is_null() && ptr as usize + layout.size() > fresh {
+            write_bytes(ptr, 0, layout.size());
+        }
+        ptr
+    }
+
+    #[inline]
+    unsafe fn deallocate(&self, ptr: *mut u8, layout: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+
+        match size_class(&layout) {
+            Some((class, block)) => {
+                // Push onto the class free list, storing the link in the block
+                *(ptr as *mut usize) = header.free[class];
+                header.free[class] = ptr as usize;
+                header.used -= block;
+            }
+            None => {
+                // Large allocations stay in the bump region
+            }
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn used(&self) -> usize {
+        // Read from the header, so recording stats never queries the region
+        if unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            unsafe { (*self.header()).used }
+        } else {
+            0
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn stats_mut(&self) -> *mut AllocStats {
+        let header = self.header();
+        unsafe {
+            if (*self.heap()).is_owned_by(BACKEND) {
+                &mut (*header).stats
+            } else {
+                null_mut()
+            }
+        }
+    }
+
+    #[inline]
+    fn failure(&self) -> Option<AllocFailure> {
+        self.last_failure()
+    }
+
+    #[inline]
+    fn oom_policy(&self) -> OomPolicy {
+        self.policy
+    }
+}
+
+unsafe impl<R: HeapRegion> GlobalAlloc for SlabAllocator<R> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        backend::alloc(self, layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        backend::alloc_zeroed(self, layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        backend::dealloc(self, ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        backend::realloc(self, ptr, layout, new_size)
+    }
+}
@@ -0,0 +1,103 @@
+//! Allocation statistics
+//!
+//! With the `stats` feature, every backend keeps an [`AllocStats`] record
+//! right after the [`HeapHeader`] and sets [`HEAP_FLAG_STATS`] in its flags.
+//! Without the feature the record is not stored and nothing is counted.
+
+#[cfg(feature = "stats")]
+use core::alloc::Layout;
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+
+use crate::header::{HeapHeader, HEAP_FLAG_STATS};
+
+/// Counters describing the allocations served from a heap
+///
+/// Fields have fixed widths, so the layout is the same on the TAKO VM and on
+/// 64-bit hosts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct AllocStats {
+    /// Most bytes in use at once, as reported by `usage()`
+    pub peak: u64,
+    /// Successful allocations
+    pub allocs: u64,
+    /// Deallocations
+    pub deallocs: u64,
+    /// Successful reallocations
+    pub reallocs: u64,
+    /// Heap bytes allocations took beyond their requested sizes: alignment
+    /// padding, rounding to the backend's block sizes and block headers
+    pub padding: u64,
+    /// Allocations and reallocations that returned null
+    pub failures: u64,
+    /// Size of the largest request that returned null
+    pub largest_failure: u64,
+    /// Size of the most recent request that returned null
+    pub last_failure_size: u64,
+    /// Alignment of the most recent request that returned null
+    pub last_failure_align: u64,
+}
+
+impl AllocStats {
+    /// Parse the statistics from a heap dump
+    ///
+    /// Returns `None` if the heap is not initialized or was initialized
+    /// without the `stats` feature.
+    pub fn read(heap: &[u8]) -> Option<Self> {
+        let header = HeapHeader::read(heap)?;
+        let offset = size_of::<HeapHeader>();
+        if header.flags & HEAP_FLAG_STATS == 0 || heap.len() < offset + size_of::<Self>() {
+            return None;
+        }
+        Some(unsafe { read_unaligned(heap[offset..].as_ptr() as *const Self) })
+    }
+
+    /// Account for an allocation returning `ptr`, which took the bytes in
+    /// use from `before` to `after`
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn record_alloc(
+        &mut self,
+        ptr: *mut u8,
+        layout: &Layout,
+        before: usize,
+        after: usize,
+    ) {
+        if ptr.is_null() {
+            self.record_failure(layout);
+            return;
+        }
+        self.allocs += 1;
+        self.padding += after.saturating_sub(before).saturating_sub(layout.size()) as u64;
+        self.peak = self.peak.max(after as u64);
+    }
+
+    /// Account for a reallocation to `new_layout` returning `ptr`, leaving
+    /// `after` bytes in use
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn record_realloc(&mut self, ptr: *mut u8, new_layout: &Layout, after: usize) {
+        if ptr.is_null() {
+            self.record_failure(new_layout);
+            return;
+        }
+        self.reallocs += 1;
+        self.peak = self.peak.max(after as u64);
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn record_dealloc(&mut self) {
+        self.deallocs += 1;
+    }
+
+    #[cfg(feature = "stats")]
+    #[cold]
+    fn record_failure(&mut self, layout: &Layout) {
+        self.failures += 1;
+        self.largest_failure = self.largest_failure.max(layout.size() as u64);
+        self.last_failure_size = layout.size() as u64;
+        self.last_failure_align = layout.align() as u64;
+    }
+}
@@ -0,0 +1,520 @@
+//! TLSF (two-level segregated fit) allocator for TOS TAKO VM
+//!
+//! Free blocks are kept in size-segregated lists indexed by a two-level
+//! bitmap, so both `alloc` and `dealloc` run in a bounded number of steps
+//! regardless of heap state. This keeps compute-unit cost predictable for
+//! contracts with heavy allocation churn.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+#[cfg(feature = "stats")]
+use core::ptr::null_mut;
+
+use crate::backend::{self, Backend};
+use crate::constants::MAX_HEAP_SIZE;
+use crate::failure::{check_layout, fail, AllocFailure};
+use crate::header::{BackendId, HeapHeader};
+use crate::oom::OomPolicy;
+use crate::region::{heap_size, HeapRegion, SyscallRegion};
+#[cfg(feature = "stats")]
+use crate::stats::AllocStats;
+
+/// log2 of the block granularity (8 bytes)
+const ALIGN_SIZE_LOG2: u32 = 3;
+const ALIGN_SIZE: usize = 1 << ALIGN_SIZE_LOG2;
+
+/// log2 of the number of second-level lists per first-level class
+const SL_INDEX_COUNT_LOG2: u32 = 3;
+const SL_INDEX_COUNT: usize = 1 << SL_INDEX_COUNT_LOG2;
+
+/// Blocks smaller than this share first-level class 0, one list per size
+const FL_INDEX_SHIFT: u32 = SL_INDEX_COUNT_LOG2 + ALIGN_SIZE_LOG2;
+const SMALL_BLOCK_SIZE: usize = 1 << FL_INDEX_SHIFT;
+
+/// Enough first-level classes for any block inside `MAX_HEAP_SIZE`
+const FL_INDEX_COUNT: usize = (MAX_HEAP_SIZE.trailing_zeros() - FL_INDEX_SHIFT + 1) as usize;
+
+/// Block flag: the block is on a free list
+const FREE: u32 = 1;
+
+const BACKEND: BackendId = BackendId::Tlsf;
+
+/// Allocator state stored at the start of the heap
+///
+/// All block references are `u32` offsets from heap start; offset 0 is
+/// the header itself and doubles as the null reference.
+#[repr(C)]
+struct Header {
+    heap: HeapHeader,
+    #[cfg(feature = "stats")]
+    stats: AllocStats,
+    /// Bytes in allocated blocks, including block headers
+    used: usize,
+    /// Address of the sentinel block, discovered on initialization
+    end: usize,
+    /// Bit `fl` set when any list in first-level class `fl` is non-empty
+    fl_bitmap: u32,
+    /// Bit `sl` of entry `fl` set when list `[fl][sl]` is non-empty
+    sl_bitmap: [u32; FL_INDEX_COUNT],
+    /// Free-list heads
+    blocks: [[u32; SL_INDEX_COUNT]; FL_INDEX_COUNT],
+}
+
+/// Block header, stored in front of every block
+///
+/// `next_free`/`prev_free` are only valid while the block is free; in an
+/// allocated block they are part of the payload.
+#[repr(C)]
+struct Block {
+    /// Offset of the physically preceding block, 0 for the first block
+    prev_phys: u32,
+    /// Total block size in bytes (multiple of `ALIGN_SIZE`) plus flags
+    size: u32,
+    next_free: u32,
+    prev_free: u32,
+}
+
+/// Bytes in front of the payload of an allocated block
+const BLOCK_OVERHEAD: usize = 2 * size_of::<u32>();
+
+/// Smallest block that can hold free-list links
+const MIN_BLOCK_SIZE: usize = size_of::<Block>();
+
+#[inline]
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+/// Index of the most significant set bit
+#[inline]
+fn fls(size: usize) -> u32 {
+    usize::BITS - 1 - size.leading_zeros()
+}
+
+/// List indices holding blocks of exactly `size`'s class
+#[inline]
+fn mapping_insert(size: usize) -> (usize, usize) {
+    if size < SMALL_BLOCK_SIZE {
+        (0, size / (SMALL_BLOCK_SIZE / SL_INDEX_COUNT))
+    } else {
+        let fl = fls(size);
+        let sl = (size >> (fl - SL_INDEX_COUNT_LOG2)) ^ SL_INDEX_COUNT;
+        ((fl - (FL_INDEX_SHIFT - 1)) as usize, sl)
+    }
+}
+
+/// List indices whose blocks are all at least `size` bytes
+#[inline]
+fn mapping_search(size: usize) -> (usize, usize) {
+    let size = if size >= SMALL_BLOCK_SIZE {
+        size + (1 << (fls(size) - SL_INDEX_COUNT_LOG2)) - 1
+    } else {
+        size
+    };
+    mapping_insert(size)
+}
+
+/// Heap-based TLSF allocator
+///
+/// **Key design decisions**:
+/// 1. **State on the heap** - Bitmaps and free-list heads live in a header
+///    at heap start instead of in globals (no writable `.data` needed). The
+///    heap size granted by the VM is discovered on first use
+/// 2. **O(1) alloc and dealloc** - A free block is found with two bitmap
+///    scans; freed blocks merge with at most two physical neighbours
+/// 3. **Compact links** - Offsets are `u32`, so each block carries only
+///    8 bytes of overhead
+///
+/// # Heap Layout
+///
+/// ```text
+/// 0x300000000: HeapHeader (magic, backend, VM heap region)
+/// 0x300000020: Bitmaps, free-list heads, used bytes
+/// 0x300000208: First block
+/// ...
+/// 0x300007ff8: Sentinel block (size 0, never free)
+/// 0x300008000: Heap top
+/// ```
+///
+/// # Usage
+///
+/// Enable the `tlsf` feature (and disable the default `bump` feature):
+///
+/// ```toml
+/// tos-alloc = { path = "../../tos-alloc", default-features = false, features = ["tlsf"] }
+/// ```
+///
+/// `TosAllocator` then refers to this allocator, so contract code is unchanged.
+pub struct TlsfAllocator<R = SyscallRegion> {
+    region: R,
+    policy: OomPolicy,
+}
+
+impl<const START: usize, const FALLBACK: usize> TlsfAllocator<SyscallRegion<START, FALLBACK>> {
+    /// Create a new allocator for the heap granted by the VM
+    pub const fn new() -> Self {
+        Self::with_region(SyscallRegion::new())
+    }
+}
+
+impl<R: HeapRegion> TlsfAllocator<R> {
+    /// Create a new allocator managing `region`
+    pub const fn with_region(region: R) -> Self {
+        Self {
+            region,
+            policy: OomPolicy::Null,
+        }
+    }
+
+    /// Handle failed requests with `policy` instead of returning null
+    pub const fn with_oom_policy(mut self, policy: OomPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get heap usage statistics
+    ///
+    /// Returns (used_bytes, remaining_bytes). Used bytes include the
+    /// per-block overhead; remaining bytes may be fragmented.
+    pub fn usage(&self) -> (usize, usize) {
+        unsafe {
+            let (used, end) = if (*self.heap()).is_owned_by(BACKEND) {
+                let header = &*self.header();
+                (header.used, header.end)
+            } else {
+                (0, self.heap_end(heap_size(&self.region)))
+            };
+            // Heaps too small for the allocator state and one block have no
+            // usable space, as `init` creates no free block for them
+            let usable = match end.checked_sub(self.base()) {
+                Some(usable) if usable >= MIN_BLOCK_SIZE => usable,
+                _ => 0,
+            };
+            (used, usable - used)
+        }
+    }
+
+    /// Header describing the heap, once the first allocation initialized it
+    pub fn heap_header(&self) -> Option<HeapHeader> {
+        let heap = unsafe { &*self.heap() };
+        heap.is_owned_by(BACKEND).then_some(*heap)
+    }
+
+    /// Why the most recent failed allocation returned null, if any failed
+    pub fn last_failure(&self) -> Option<AllocFailure> {
+        unsafe { (*self.heap()).last_failure_of(BACKEND) }
+    }
+
+    /// Allocation statistics since the heap was initialized
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        unsafe { self.stats_mut().as_ref().copied().unwrap_or_default() }
+    }
+
+    /// Heap header, which every region is large enough to hold
+    #[inline]
+    fn heap(&self) -> *mut HeapHeader {
+        self.region.start() as *mut HeapHeader
+    }
+
+    /// Allocator state, only valid once the heap header says this
+    /// allocator laid it out
+    #[inline]
+    fn header(&self) -> *mut Header {
+        self.region.start() as *mut Header
+    }
+
+    #[inline]
+    fn base(&self) -> usize {
+        (self.region.start() + size_of::<Header>() + ALIGN_SIZE - 1) & !(ALIGN_SIZE - 1)
+    }
+
+    /// Address of the sentinel block, at the top of a heap of `len` bytes
+    #[inline]
+    fn heap_end(&self, len: usize) -> usize {
+        ((self.region.start() + len) & !(ALIGN_SIZE - 1)) - BLOCK_OVERHEAD
+    }
+
+    #[inline]
+    fn block(&self, offset: u32) -> *mut Block {
+        (self.region.start() + offset as usize) as *mut Block
+    }
+
+    #[inline]
+    fn offset(&self, addr: usize) -> u32 {
+        (addr - self.region.start()) as u32
+    }
+
+    /// Create one free block spanning the heap, followed by the sentinel
+    unsafe fn init(&self, header: &mut Header, len: usize) {
+        let start = self.region.start();
+        let base = self.base();
+        let end = self.heap_end(len);
+        header.end = end;
+        // Heaps with no room for one block past the state stay empty
+        if end >= base + MIN_BLOCK_SIZE {
+            let sentinel = self.block(self.offset(end));
+            (*sentinel).prev_phys = self.offset(base);
+            (*sentinel).size = 0;
+
+            let first = self.block(self.offset(base));
+            (*first).prev_phys = 0;
+            (*first).size = (end - base) as u32 | FREE;
+            self.insert_free(header, self.offset(base));
+        }
+
+        header.heap.init(BACKEND, base - start, start, len);
+    }
+
+    /// Allocator state, laid out on first use
+    ///
+    /// Nothing past the heap header is read or written unless the region
+    /// can hold the whole state.
+    #[inline]
+    unsafe fn state(&self) -> Result<*mut Header, AllocFailure> {
+        if (*self.heap()).needs_init(BACKEND)? {
+            let len = heap_size(&self.region);
+            if len < size_of::<Header>() {
+                return Err(AllocFailure::SizeTooLarge);
+            }
+            // Statistics under a zeroed heap header mean it was wiped
+            #[cfg(feature = "stats")]
+            if (*self.header()).stats != AllocStats::default() {
+                return Err(AllocFailure::HeapCorrupted);
+            }
+            self.init(&mut *self.header(), len);
+        }
+        Ok(self.header())
+    }
+
+    unsafe fn insert_free(&self, header: &mut Header, offset: u32) {
+        let block = self.block(offset);
+        let (fl, sl) = mapping_insert(((*block).size & !FREE) as usize);
+        let head = header.blocks[fl][sl];
+
+        (*block).next_free = head;
+        (*block).prev_free = 0;
+        if head != 0 {
+            (*self.block(head)).prev_free = offset;
+        }
+        header.blocks[fl][sl] = offset;
+        header.sl_bitmap[fl] |= 1 << sl;
+        header.fl_bitmap |= 1 << fl;
+    }
+
+    unsafe fn remove_free(&self, header: &mut Header, offset: u32) {
+        let block = self.block(offset);
+        let (fl, sl) = mapping_insert(((*block).size & !FREE) as usize);
+        let next = (*block).next_free;
+        let prev = (*block).prev_free;
+
+        if next != 0 {
+            (*self.block(next)).prev_free = prev;
+        }
+        if prev != 0 {
+            (*self.block(prev)).next_free = next;
+        } else {
+            header.blocks[fl][sl] = next;
+            if next == 0 {
+                header.sl_bitmap[fl] &= !(1 << sl);
+                if header.sl_bitmap[fl] == 0 {
+                    header.fl_bitmap &= !(1 << fl);
+                }
+            }
+        }
+    }
+
+    /// Find a free block of at least `size` bytes
+    fn find_free(&self, header: &Header, size: usize) -> Option<u32> {
+        let (mut fl, sl) = mapping_search(size);
+        if fl >= FL_INDEX_COUNT {
+            return None;
+        }
+
+        let mut sl_map = header.sl_bitmap[fl] & (!0u32 << sl);
+        if sl_map == 0 {
+            let fl_map = header.fl_bitmap & (!0u32).checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return None;
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = header.sl_bitmap[fl];
+        }
+
+        Some(header.blocks[fl][sl_map.trailing_zeros() as usize])
+    }
+
+    /// Shrink an unlinked block to `size`, freeing the remainder
+    unsafe fn split(&self, header: &mut Header, offset: u32, size: usize) {
+        let block = self.block(offset);
+        let block_size = ((*block).size & !FREE) as usize;
+        if block_size - size < MIN_BLOCK_SIZE {
+            return;
+        }
+
+        let rest_offset = offset + size as u32;
+        let rest = self.block(rest_offset);
+        (*rest).prev_phys = offset;
+        (*rest).size = (block_size - size) as u32 | FREE;
+        (*self.block(offset + block_size as u32)).prev_phys = rest_offset;
+        (*block).size = size as u32;
+        self.insert_free(header, rest_offset);
+    }
+}
+
+impl<R: HeapRegion + Default> Default for TlsfAllocator<R> {
+    fn default() -> Self {
+        Self::with_region(R::default())
+    }
+}
+
+impl<R: HeapRegion> Backend for TlsfAllocator<R> {
+    unsafe fn allocate(&self, layout: Layout) -> *mut u8 {
+        let header = match self.state() {
+            Ok(header) => &mut *header,
+            Err(failure) => return fail(&mut *self.heap(), failure),
+        };
+
+        if let Err(failure) = check_layout(&layout, header.end.saturating_sub(self.base())) {
+            return fail(&mut header.heap, failure);
+        }
+
+        let size = match align_up(layout.size().max(1), ALIGN_SIZE) {
+            Some(size) if size <= MAX_HEAP_SIZE => (size + BLOCK_OVERHEAD).max(MIN_BLOCK_SIZE),
+            _ => return fail(&mut header.heap, AllocFailure::SizeTooLarge),
+        };
+        let align = layout.align();
+
+        // Over-aligned requests reserve room to trim a free block off the front
+        let search = if align <= ALIGN_SIZE {
+            size
+        } else {
+            size + align + MIN_BLOCK_SIZE
+        };
+
+        let mut offset = match self.find_free(header, search) {
+            Some(offset) => offset,
+            None => return fail(&mut header.heap, AllocFailure::OutOfMemory),
+        };
+        self.remove_free(header, offset);
+
+        if align > ALIGN_SIZE {
+            let payload = self.region.start() + offset as usize + BLOCK_OVERHEAD;
+            let mut gap = align_up(payload, align).unwrap_or(payload) - payload;
+            if gap != 0 && gap < MIN_BLOCK_SIZE {
+                gap += align;
+            }
+
+            if gap != 0 {
+                // The previous physical block is in use, so the gap cannot merge
+                let block = self.block(offset);
+                let block_size = ((*block).size & !FREE) as usize;
+                let aligned_offset = offset + gap as u32;
+                let aligned = self.block(aligned_offset);
+                (*aligned).prev_phys = offset;
+                (*aligned).size = (block_size - gap) as u32;
+                (*self.block(offset + block_size as u32)).prev_phys = aligned_offset;
+                (*block).size = gap as u32 | FREE;
+                self.insert_free(header, offset);
+                offset = aligned_offset;
+            }
+        }
+
+        self.split(header, offset, size);
+        let block = self.block(offset);
+        (*block).size &= !FREE;
+        header.used += (*block).size as usize;
+
+        (self.region.start() + offset as usize + BLOCK_OVERHEAD) as *mut u8
+    }
+
+    unsafe fn deallocate(&self, ptr: *mut u8, _: Layout) {
+        if !(*self.heap()).is_owned_by(BACKEND) {
+            return;
+        }
+        let header = &mut *self.header();
+        let mut offset = self.offset(ptr as usize - BLOCK_OVERHEAD);
+        let block = self.block(offset);
+        let mut size = (*block).size as usize;
+        header.used -= size;
+
+        // Merge with the following block (the sentinel is never free)
+        let next_offset = offset + size as u32;
+        let next = self.block(next_offset);
+        if (*next).size & FREE != 0 {
+            self.remove_free(header, next_offset);
+            size += ((*next).size & !FREE) as usize;
+        }
+
+        // Merge with the preceding block
+        let prev_offset = (*block).prev_phys;
+        if prev_offset != 0 {
+            let prev = self.block(prev_offset);
+            if (*prev).size & FREE != 0 {
+                self.remove_free(header, prev_offset);
+                size += ((*prev).size & !FREE) as usize;
+                offset = prev_offset;
+            }
+        }
+
+        (*self.block(offset)).size = size as u32 | FREE;
+        (*self.block(offset + size as u32)).prev_phys = offset;
+        self.insert_free(header, offset);
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn used(&self) -> usize {
+        // Read from the header, so recording stats never queries the region
+        if unsafe { (*self.heap()).is_owned_by(BACKEND) } {
+            unsafe { (*self.header()).used }
+        } else {
+            0
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn stats_mut(&self) -> *mut AllocStats {
+        let header = self.header();
+        unsafe {
+            if (*self.heap()).is_owned_by(BACKEND) {
+                &mut (*header).stats
+            } else {
+                null_mut()
+            }
+        }
+    }
+
+    #[inline]
+    fn failure(&self) -> Option<AllocFailure> {
+        self.last_failure()
+    }
+
+    #[inline]
+    fn oom_policy(&self) -> OomPolicy {
+        self.policy
+    }
+}
+
+unsafe impl<R: HeapRegion> GlobalAlloc for TlsfAllocator<R> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        backend::alloc(self, layout)
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        backend::alloc_zeroed(self, layout)
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        backend::dealloc(self, ptr, layout)
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        backend::realloc(self, ptr, layout, new_size)
+    }
+}
@@ -0,0 +1,94 @@
+//! Typed allocation on the bump heap
+//!
+//! Contracts that do not install a `#[global_allocator]` can still place
+//! values on the heap through these methods. Values live as long as the
+//! borrow of the allocator and are never dropped, so there is no `Layout`
+//! boilerplate and no drop glue:
+//!
+//! ```ignore
+//! static HEAP: BumpAllocator = BumpAllocator::new();
+//!
+//! let header = HEAP.alloc_value(Header::parse(input));
+//! let name = HEAP.alloc_str("transfer");
+//! let squares = HEAP.alloc_slice_fill_with(16, |i| i * i);
+//! ```
+//!
+//! A request the heap cannot serve goes through the allocator's OOM policy
+//! and then `handle_alloc_error`, like `Box::new`.
+
+use alloc::alloc::handle_alloc_error;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::align_of;
+use core::ptr::NonNull;
+use core::slice;
+use core::str;
+
+use crate::bump::BumpAllocator;
+use crate::region::HeapRegion;
+
+// Nothing here frees a block, and the allocator only hands one out again
+// after `reset_to`, which is unsafe and requires the block to be unused, or
+// when a `scope` ends with nothing but its arena allocated since it began.
+// So every `&mut` returned from `&self` stays unique
+#[allow(clippy::mut_from_ref)]
+impl<R: HeapRegion> BumpAllocator<R> {
+    /// Move `value` to the heap
+    pub fn alloc_value<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_block(Layout::new::<T>()).cast::<T>();
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copy `src` to the heap
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let ptr = self.alloc_block(Layout::for_value(src)).cast::<T>();
+        unsafe {
+            ptr.as_ptr()
+                .copy_from_nonoverlapping(src.as_ptr(), src.len());
+            slice::from_raw_parts_mut(ptr.as_ptr(), src.len())
+        }
+    }
+
+    /// Copy `src` to the heap
+    pub fn alloc_str(&self, src: &str) -> &mut str {
+        let bytes = self.alloc_slice_copy(src.as_bytes());
+        unsafe { str::from_utf8_unchecked_mut(bytes) }
+    }
+
+    /// Allocate a slice of `len` values, the value at index `i` being `f(i)`
+    ///
+    /// A `len` whose size overflows fails like any request larger than the
+    /// heap.
+    pub fn alloc_slice_fill_with<T>(&self, len: usize, mut f: impl FnMut(usize) -> T) -> &mut [T] {
+        let layout = Layout::array::<T>(len).unwrap_or_else(|_| oversized::<T>());
+        let ptr = self.alloc_block(layout).cast::<T>();
+        unsafe {
+            for i in 0..len {
+                ptr.as_ptr().add(i).write(f(i));
+            }
+            slice::from_raw_parts_mut(ptr.as_ptr(), len)
+        }
+    }
+
+    /// Allocate a block that is never freed
+    #[inline]
+    fn alloc_block(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            // Zero-sized values take no heap, any aligned address works
+            return unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+        }
+        let ptr = unsafe { self.alloc(layout) };
+        NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout))
+    }
+}
+
+/// Largest layout with `T`'s alignment, which no heap can serve
+///
+/// Stands in for a size that overflows, so the request still fails through
+/// the OOM policy and `handle_alloc_error` with `SizeTooLarge`.
+fn oversized<T>() -> Layout {
+    let align = align_of::<T>();
+    unsafe { Layout::from_size_align_unchecked(isize::MAX as usize + 1 - align, align) }
+}
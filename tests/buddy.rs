@@ -0,0 +1,123 @@
+//! Host tests for the buddy allocator
+//!
+//! The allocator is pointed at a host buffer instead of the TAKO VM heap.
+
+#![cfg(feature = "buddy")]
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{BuddyAllocator, HostRegion};
+
+const HEAP_SIZE: usize = 8192;
+
+#[repr(C, align(8192))]
+struct Heap([u8; HEAP_SIZE]);
+
+fn allocator(heap: &mut Heap) -> BuddyAllocator<HostRegion> {
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), HEAP_SIZE) };
+    BuddyAllocator::with_region(region)
+}
+
+#[test]
+fn test_dealloc_reuses_memory() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(2048, 8).unwrap();
+
+    unsafe {
+        let first = allocator.alloc(layout);
+        assert!(!first.is_null());
+        allocator.dealloc(first, layout);
+
+        for _ in 0..100 {
+            let ptr = allocator.alloc(layout);
+            assert_eq!(ptr, first);
+            allocator.dealloc(ptr, layout);
+        }
+    }
+}
+
+#[test]
+fn test_buddies_coalesce() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let small = Layout::from_size_align(16, 8).unwrap();
+    let half = Layout::from_size_align(HEAP_SIZE / 2, 8).unwrap();
+
+    unsafe {
+        // The upper half of the heap is one 4 KB block until split
+        let upper = allocator.alloc(half);
+        assert_eq!(upper as usize, heap.0.as_ptr() as usize + HEAP_SIZE / 2);
+        allocator.dealloc(upper, half);
+
+        let ptrs: Vec<*mut u8> = (0..200).map(|_| allocator.alloc(small)).collect();
+        assert!(ptrs.iter().all(|ptr| !ptr.is_null()));
+        for &ptr in ptrs.iter().rev() {
+            allocator.dealloc(ptr, small);
+        }
+
+        // All 16-byte blocks merged back into the 4 KB block
+        assert_eq!(allocator.alloc(half), upper);
+    }
+}
+
+#[test]
+fn test_blocks_are_aligned_to_size() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        for (size, align) in [(1, 1), (24, 8), (100, 64), (16, 512), (1000, 8)] {
+            let ptr = allocator.alloc(Layout::from_size_align(size, align).unwrap());
+            assert!(!ptr.is_null());
+            let block = size.max(align).next_power_of_two();
+            assert_eq!(ptr as usize % block, 0);
+        }
+    }
+}
+
+#[test]
+fn test_out_of_memory_returns_null() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        // The header takes the first block, so the whole heap is never free
+        let layout = Layout::from_size_align(HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        // Heap start is not aligned this strictly
+        let layout = Layout::from_size_align(8, HEAP_SIZE * 2).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        let layout = Layout::from_size_align(1024, 8).unwrap();
+        let mut count = 0;
+        while !allocator.alloc(layout).is_null() {
+            count += 1;
+        }
+        assert_eq!(count, 7);
+    }
+}
+
+#[test]
+fn test_tiny_heap_has_no_usable_space() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    // The free bitmap shrinks with the heap, so measure the state of a heap
+    // that barely holds it
+    let mut header_len = HEAP_SIZE;
+    for _ in 0..2 {
+        let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), header_len) };
+        let probe = BuddyAllocator::with_region(region);
+        unsafe { probe.alloc(Layout::new::<u8>()) };
+        header_len = probe.heap_header().unwrap().header_len as usize;
+        heap.0.fill(0);
+    }
+
+    // Room for the allocator state but not for a single block
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), header_len + 4) };
+    let allocator = BuddyAllocator::with_region(region);
+    unsafe {
+        assert_eq!(allocator.usage(), (0, 0));
+        assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        assert_eq!(allocator.usage(), (0, 0));
+    }
+}
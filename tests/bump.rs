@@ -0,0 +1,489 @@
+//! Host tests for the bump allocator
+//!
+//! The allocator is pointed at a host buffer instead of the TAKO VM heap.
+
+#![cfg(feature = "bump")]
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{AllocFailure, BumpAllocator, HostRegion, OomPolicy};
+
+const HEAP_SIZE: usize = 4096;
+
+/// Bytes of allocator state at heap start
+const HEADER: usize = if cfg!(feature = "stats") { 128 } else { 56 };
+
+#[repr(C, align(4096))]
+struct Heap<const N: usize = HEAP_SIZE>([u8; N]);
+
+fn allocator<const N: usize>(heap: &mut Heap<N>) -> BumpAllocator<HostRegion> {
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), N) };
+    BumpAllocator::with_region(region)
+}
+
+#[test]
+fn test_tiny_heap_has_no_usable_space() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+
+    // Only room for the heap header
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), 32) };
+    assert_eq!(BumpAllocator::with_region(region).usage(), (0, 0));
+
+    // Room for the allocator state but not for a single word
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), HEADER + 4) };
+    let allocator = BumpAllocator::with_region(region);
+    unsafe {
+        assert_eq!(allocator.usage(), (0, 0));
+        assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        assert_eq!(allocator.usage(), (0, 0));
+    }
+}
+
+#[test]
+fn test_dealloc_last_allocation_reclaims() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(1000, 8).unwrap();
+
+    unsafe {
+        let first = allocator.alloc(layout);
+        assert!(!first.is_null());
+        allocator.dealloc(first, layout);
+
+        // Far more than the heap holds, unless the memory is reused
+        for _ in 0..100 {
+            let ptr = allocator.alloc(layout);
+            assert_eq!(ptr, first);
+            allocator.dealloc(ptr, layout);
+        }
+    }
+}
+
+#[test]
+fn test_push_drop_loop_does_not_leak() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let long_lived = Layout::from_size_align(64, 8).unwrap();
+
+    unsafe {
+        let kept = allocator.alloc(long_lived);
+        assert!(!kept.is_null());
+        let probe = allocator.alloc(long_lived);
+        allocator.dealloc(probe, long_lived);
+
+        // Nested temporaries (e.g. `Vec::with_capacity` plus a scratch
+        // buffer) dropped in reverse order at the end of every iteration
+        for i in 0..1000 {
+            let outer = Layout::array::<u32>(64 + i % 8).unwrap();
+            let inner = Layout::from_size_align(100, 4).unwrap();
+            let buffer = allocator.alloc(outer);
+            let scratch = allocator.alloc(inner);
+            assert!(!buffer.is_null() && !scratch.is_null());
+            allocator.dealloc(scratch, inner);
+            allocator.dealloc(buffer, outer);
+        }
+
+        // Everything after the long-lived block was handed back
+        assert_eq!(allocator.alloc(long_lived), probe);
+    }
+}
+
+/// Regression test: without word rounding, a block whose size is not a
+/// multiple of the next block's alignment leaves the position between them,
+/// so freeing the later block no longer reaches the earlier one and LIFO
+/// reclaim stops there
+#[test]
+fn test_lifo_chain_with_odd_sizes_reclaims_everything() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layouts = [
+        Layout::from_size_align(3, 1).unwrap(),
+        Layout::from_size_align(13, 4).unwrap(),
+        Layout::from_size_align(7, 8).unwrap(),
+        Layout::from_size_align(1, 2).unwrap(),
+        Layout::from_size_align(17, 8).unwrap(),
+    ];
+
+    unsafe {
+        let mut used = [0; 5];
+        let mut ptrs = [core::ptr::null_mut(); 5];
+        for (i, &layout) in layouts.iter().enumerate() {
+            used[i] = allocator.usage().0;
+            ptrs[i] = allocator.alloc(layout);
+            assert!(!ptrs[i].is_null());
+        }
+
+        // Every free hands back exactly what its allocation took
+        for i in (0..layouts.len()).rev() {
+            allocator.dealloc(ptrs[i], layouts[i]);
+            assert_eq!(allocator.usage().0, used[i]);
+        }
+        assert_eq!(allocator.usage().0, 0);
+    }
+}
+
+#[test]
+fn test_dealloc_older_allocation_is_noop() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        allocator.dealloc(a, layout);
+
+        let c = allocator.alloc(layout);
+        assert_ne!(c, a);
+        assert_ne!(c, b);
+    }
+}
+
+#[test]
+fn test_realloc_grows_last_allocation_without_leaking() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        let mut capacity = 4;
+        let mut buffer = allocator.alloc(Layout::array::<u32>(capacity).unwrap()) as *mut u32;
+        let first = buffer;
+        buffer.write(7);
+
+        // Doubling up to 2 KB would need ~4 KB if every old block leaked
+        while capacity < 512 {
+            let old = Layout::array::<u32>(capacity).unwrap();
+            buffer = allocator.realloc(buffer as *mut u8, old, old.size() * 2) as *mut u32;
+            assert!(!buffer.is_null());
+            assert_eq!(buffer.read(), 7);
+            buffer.add(capacity).write(capacity as u32);
+            capacity *= 2;
+        }
+        assert_eq!(buffer.add(256).read(), 256);
+
+        if cfg!(feature = "bump-upward") {
+            // Grown in place, never moved
+            assert_eq!(buffer, first);
+        } else {
+            // Extended downward from the original block's top
+            assert_eq!(buffer as usize + capacity * 4, first as usize + 16);
+        }
+    }
+}
+
+#[test]
+fn test_realloc_shrinks_in_place() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(256, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        let _b = allocator.alloc(layout);
+        assert_eq!(allocator.realloc(a, layout, 16), a);
+    }
+}
+
+#[test]
+fn test_realloc_shrinks_last_allocation_in_place() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(256, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        for i in 0..256 {
+            *a.add(i) = i as u8;
+        }
+
+        // The block keeps its start, so nothing is copied
+        let shrunk = allocator.realloc(a, layout, 20);
+        assert_eq!(shrunk, a);
+        assert!((0..20).all(|i| *shrunk.add(i) == i as u8));
+    }
+}
+
+#[test]
+fn test_realloc_moves_older_allocation() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        a.write_bytes(0xab, 16);
+        let b = allocator.alloc(layout);
+
+        let grown = allocator.realloc(a, layout, 64);
+        assert!(!grown.is_null());
+        assert_ne!(grown, a);
+        assert_ne!(grown, b);
+        assert_eq!(*grown.add(15), 0xab);
+    }
+}
+
+#[test]
+fn test_realloc_out_of_memory_keeps_block() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        ptr.write_bytes(0xcd, 64);
+        assert!(allocator.realloc(ptr, layout, HEAP_SIZE).is_null());
+        assert_eq!(*ptr, 0xcd);
+
+        // The failed request did not move the position
+        allocator.dealloc(ptr, layout);
+        assert_eq!(allocator.alloc(layout), ptr);
+    }
+}
+
+#[cfg(feature = "bump-upward")]
+#[test]
+fn test_upward_allocations_ascend() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(24, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        assert_eq!(a as usize, heap.0.as_ptr() as usize + HEADER);
+        assert_eq!(b as usize, a as usize + 24);
+
+        // Over-aligned requests skip forward
+        let c = allocator.alloc(Layout::from_size_align(8, 256).unwrap());
+        assert_eq!(c as usize % 256, 0);
+        assert!(c > b);
+    }
+}
+
+#[cfg(feature = "bump-upward")]
+#[test]
+fn test_upward_realloc_shrink_returns_tail() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(1024, 8).unwrap();
+    let small = Layout::from_size_align(8, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        assert_eq!(allocator.realloc(a, layout, 16), a);
+        assert_eq!(allocator.alloc(small) as usize, a as usize + 16);
+    }
+}
+
+#[test]
+fn test_heap_is_capped_at_max_heap_size() {
+    const LARGE: usize = 2 * tos_alloc::MAX_HEAP_SIZE;
+    let mut heap = Box::new(Heap([0; LARGE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        let layout = Layout::from_size_align(tos_alloc::MAX_HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        let layout = Layout::from_size_align(tos_alloc::MAX_HEAP_SIZE - 1024, 8).unwrap();
+        let ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+        assert!((ptr as usize) < heap.0.as_ptr() as usize + tos_alloc::MAX_HEAP_SIZE);
+    }
+}
+
+#[test]
+fn test_usage_reflects_instance() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        assert_eq!(allocator.usage(), (0, HEAP_SIZE - HEADER));
+
+        // Blocks are rounded up to whole words
+        let layout = Layout::from_size_align(100, 4).unwrap();
+        let ptr = allocator.alloc(layout);
+        assert_eq!(allocator.usage(), (104, HEAP_SIZE - HEADER - 104));
+
+        allocator.dealloc(ptr, layout);
+        assert_eq!(allocator.usage(), (0, HEAP_SIZE - HEADER));
+    }
+}
+
+#[test]
+fn test_realloc_rejects_oversized_request() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        let before = allocator.usage();
+        assert!(allocator.realloc(ptr, layout, usize::MAX / 4).is_null());
+        assert_eq!(allocator.last_failure(), Some(AllocFailure::SizeTooLarge));
+        assert_eq!(allocator.usage(), before);
+    }
+}
+
+#[cfg(not(feature = "bump-upward"))]
+#[test]
+fn test_alloc_zeroed_skips_fresh_memory() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let top = heap.0.as_mut_ptr() as usize + HEAP_SIZE;
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    unsafe {
+        // A marker in memory never handed out survives, proving no memset
+        *((top - 1) as *mut u8) = 0xaa;
+        let ptr = allocator.alloc_zeroed(layout);
+        assert_eq!(ptr as usize, top - 64);
+        assert_eq!(*ptr.add(63), 0xaa);
+
+        // Once handed out, the memory is cleared again
+        allocator.dealloc(ptr, layout);
+        let ptr = allocator.alloc_zeroed(layout);
+        assert_eq!(*ptr.add(63), 0);
+    }
+}
+
+#[test]
+fn test_reset_to_checkpoint_releases_phase() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(100, 8).unwrap();
+
+    unsafe {
+        // Taken before the first allocation, it releases everything
+        let start = allocator.checkpoint();
+        let kept = allocator.alloc(layout);
+        let checkpoint = allocator.checkpoint();
+        let usage = allocator.usage();
+
+        // A parsing phase leaving garbage in any order
+        for _ in 0..10 {
+            assert!(!allocator.alloc(layout).is_null());
+        }
+        allocator.reset_to(checkpoint);
+        assert_eq!(allocator.usage(), usage);
+        assert_ne!(allocator.alloc(layout), kept);
+
+        // Resetting to a later checkpoint than the position is a no-op
+        let later = allocator.checkpoint();
+        allocator.reset_to(checkpoint);
+        allocator.reset_to(later);
+        assert_eq!(allocator.usage(), usage);
+
+        allocator.reset_to(start);
+        assert_eq!(allocator.usage(), (0, HEAP_SIZE - HEADER));
+    }
+}
+
+#[test]
+fn test_scope_releases_arena_allocations() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    let sum = allocator.scope(|arena| {
+        let mut sum = 0;
+        for i in 0..100u64 {
+            sum += *arena.alloc(i).unwrap();
+        }
+        sum
+    });
+    assert_eq!(sum, 4950);
+    assert_eq!(allocator.usage(), (0, HEAP_SIZE - HEADER));
+
+    // Values larger than the heap fail instead of panicking
+    assert!(allocator.scope(|arena| arena.alloc([0u8; HEAP_SIZE]).is_none()));
+}
+
+#[test]
+fn test_scope_keeps_blocks_allocated_outside_arena() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    unsafe {
+        // Allocated inside the scope, but not through the arena
+        let escaped = allocator.scope(|arena| {
+            arena.alloc(1u64).unwrap();
+            allocator.alloc(layout)
+        });
+        let usage = allocator.usage();
+        assert!(usage.0 >= 64);
+
+        // ...even when the arena allocates after it
+        allocator.scope(|arena| {
+            allocator.alloc(layout);
+            arena.alloc(1u64).unwrap();
+        });
+        assert!(allocator.usage().0 > usage.0);
+
+        // Blocks freed in LIFO order within the scope do not count
+        let usage = allocator.usage();
+        allocator.scope(|arena| {
+            let temporary = allocator.alloc(layout);
+            allocator.dealloc(temporary, layout);
+            arena.alloc(1u64).unwrap();
+        });
+        assert_eq!(allocator.usage(), usage);
+        escaped.write_bytes(0xaa, 64);
+    }
+}
+
+#[test]
+fn test_typed_allocations() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    let value = allocator.alloc_value(0x1234_5678u64);
+    let name = allocator.alloc_str("transfer");
+    let amounts = allocator.alloc_slice_copy(&[1u32, 2, 3]);
+    let squares = allocator.alloc_slice_fill_with(16, |i| (i * i) as u16);
+    let empty = allocator.alloc_slice_copy::<u64>(&[]);
+
+    // Every reference stays valid and distinct while the others are used
+    *value += 1;
+    name.make_ascii_uppercase();
+    amounts[2] = 30;
+    assert_eq!(*value, 0x1234_5679);
+    assert_eq!(name, "TRANSFER");
+    assert_eq!(amounts, &[1, 2, 30]);
+    assert_eq!(squares[15], 225);
+    assert!(empty.is_empty());
+    assert_eq!(value as *mut u64 as usize % 8, 0);
+    assert_eq!(squares.as_ptr() as usize % 2, 0);
+
+    let (used, _) = allocator.usage();
+    assert_eq!(used, 8 + 8 + 16 + 32);
+
+    // Zero-sized values take no heap
+    allocator.alloc_value(());
+    allocator.alloc_slice_fill_with(100, |_| ());
+    assert_eq!(allocator.usage().0, used);
+}
+
+#[test]
+#[should_panic(expected = "SizeTooLarge")]
+fn test_typed_slice_overflow_goes_through_oom_policy() {
+    fn refuse(_: Layout, failure: AllocFailure) -> bool {
+        panic!("{failure:?}");
+    }
+
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap).with_oom_policy(OomPolicy::Handler(refuse));
+    allocator.alloc_slice_fill_with(usize::MAX / 2, |_| 0u64);
+}
+
+#[test]
+fn test_scope_keeps_typed_allocations() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    let kept = allocator.scope(|arena| {
+        arena.alloc(1u64).unwrap();
+        allocator.alloc_value(7u64)
+    });
+    allocator.alloc_slice_fill_with(64, |_| 0xffu8);
+    assert_eq!(*kept, 7);
+}
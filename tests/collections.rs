@@ -0,0 +1,181 @@
+//! Host tests for the heap-efficient collections
+//!
+//! `BumpVec` and `BumpString` run on a bump allocator over a `HostHeap`;
+//! `VecMap` and `VecSet` use the test's global allocator.
+
+use tos_alloc::collections::{VecMap, VecSet};
+#[cfg(feature = "bump")]
+use tos_alloc::{
+    collections::{BumpString, BumpVec},
+    BumpAllocator, HostHeap,
+};
+
+#[cfg(feature = "bump")]
+const HEAP_SIZE: usize = 4096;
+
+#[cfg(feature = "bump")]
+#[test]
+fn test_bump_vec_reuses_its_block() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = BumpAllocator::with_region(&heap);
+    let _ = allocator.alloc_value(0u64);
+    let (before, _) = allocator.usage();
+
+    let mut numbers = BumpVec::new_in(&allocator);
+    for i in 0..100u32 {
+        numbers.push(i);
+    }
+    assert_eq!(numbers.iter().sum::<u32>(), 4950);
+
+    // Every growth resized the same block, so only the final buffer is used
+    let cap = numbers.capacity();
+    assert_eq!(allocator.usage().0 - before, cap * 4);
+
+    // Dropping the most recent allocation gives all of it back
+    drop(numbers);
+    assert_eq!(allocator.usage().0, before);
+}
+
+#[cfg(feature = "bump-upward")]
+#[test]
+fn test_bump_vec_keeps_its_buffer_growing_upward() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = BumpAllocator::with_region(&heap);
+
+    let mut numbers = BumpVec::new_in(&allocator);
+    numbers.push(0u32);
+    let start = numbers.as_ptr();
+    for i in 1..500 {
+        numbers.push(i);
+        assert_eq!(numbers.as_ptr(), start, "buffer moved at {i}");
+    }
+
+    numbers.truncate(10);
+    numbers.shrink_to_fit();
+    assert_eq!(numbers.as_ptr(), start);
+}
+
+#[cfg(feature = "bump")]
+#[test]
+fn test_bump_vec_moves_past_other_allocations() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = BumpAllocator::with_region(&heap);
+
+    let mut values = BumpVec::with_capacity_in(4, &allocator);
+    values.extend_from_slice(&[1u64, 2, 3, 4]);
+    let pinned = allocator.alloc_value(7u64);
+
+    // The buffer is no longer the most recent block, so it moves
+    values.extend(5..=8);
+    assert_eq!(values, [1, 2, 3, 4, 5, 6, 7, 8][..]);
+    assert_eq!(*pinned, 7);
+
+    assert_eq!(values.pop(), Some(8));
+    values.truncate(2);
+    assert_eq!(values, [1, 2][..]);
+
+    // Zero-sized values never touch the heap
+    let (used, _) = allocator.usage();
+    let mut units = BumpVec::new_in(&allocator);
+    for _ in 0..1000 {
+        units.push(());
+    }
+    assert_eq!(units.len(), 1000);
+    assert_eq!(allocator.usage().0, used);
+}
+
+#[cfg(feature = "bump")]
+#[test]
+fn test_bump_slice_keeps_its_values() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = BumpAllocator::with_region(&heap);
+    let (before, _) = allocator.usage();
+
+    let mut squares = BumpVec::with_capacity_in(64, &allocator);
+    squares.extend((0..10u64).map(|i| i * i));
+    let squares = squares.into_bump_slice();
+    assert_eq!(squares[9], 81);
+
+    // Growing upward, the spare tail is above the position and comes back;
+    // growing downward it stays with the slice
+    let kept = if cfg!(feature = "bump-upward") {
+        10
+    } else {
+        64
+    };
+    assert_eq!(allocator.usage().0 - before, kept * 8);
+
+    // The slice is kept, later allocations go after it
+    let next = allocator.alloc_value(1u64);
+    assert_eq!(*next, 1);
+    assert_eq!(squares.iter().sum::<u64>(), 285);
+}
+
+#[cfg(feature = "bump")]
+#[test]
+fn test_bump_string() {
+    use core::fmt::Write;
+
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = BumpAllocator::with_region(&heap);
+
+    let mut memo = BumpString::from_str_in("pay", &allocator);
+    memo.push(' ');
+    write!(memo, "{} to bob", 250).unwrap();
+    memo.push('é');
+    assert_eq!(memo, "pay 250 to bobé");
+    assert_eq!(memo.pop(), Some('é'));
+    assert!(memo.ends_with("bob"));
+
+    let memo = memo.into_bump_str();
+    assert_eq!(memo, "pay 250 to bob");
+}
+
+#[test]
+fn test_vec_map_matches_btree_map() {
+    let mut map = VecMap::new();
+    assert_eq!(map.insert(3u32, "c"), None);
+    assert_eq!(map.insert(1, "a"), None);
+    assert_eq!(map.insert(2, "b"), None);
+    assert_eq!(map.insert(2, "B"), Some("b"));
+
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get(&2), Some(&"B"));
+    assert_eq!(map[&3], "c");
+    assert!(!map.contains_key(&4));
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), [1, 2, 3]);
+    assert_eq!(map.first_key_value(), Some((&1, &"a")));
+
+    *map.get_mut(&1).unwrap() = "A";
+    assert_eq!(map.remove(&3), Some("c"));
+    assert_eq!(map.remove(&3), None);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"A"), (&2, &"B")]);
+
+    // Later duplicates win, as with `BTreeMap`
+    let map: VecMap<_, _> = [(5, 'x'), (4, 'y'), (5, 'z')].into_iter().collect();
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), [(4, 'y'), (5, 'z')]);
+
+    // Keys can be looked up through `Borrow`
+    let mut names = VecMap::new();
+    names.insert(String::from("bob"), 2);
+    names.insert(String::from("alice"), 1);
+    assert_eq!(names.get("alice"), Some(&1));
+    assert_eq!(format!("{names:?}"), r#"{"alice": 1, "bob": 2}"#);
+}
+
+#[test]
+fn test_vec_set_matches_btree_set() {
+    let mut set: VecSet<_> = [8u8, 3, 5, 3].into_iter().collect();
+    assert_eq!(set.as_slice(), [3, 5, 8]);
+
+    assert!(set.insert(1));
+    assert!(!set.insert(5));
+    assert!(set.contains(&8));
+    assert!(set.remove(&8));
+    assert!(!set.remove(&8));
+
+    set.extend([9, 1, 4]);
+    assert_eq!(set.iter().copied().collect::<Vec<_>>(), [1, 3, 4, 5, 9]);
+    assert_eq!(set.pop_first(), Some(1));
+    assert_eq!(set.last(), Some(&9));
+}
@@ -0,0 +1,29 @@
+//! Host tests for OOM diagnostics
+//!
+//! The log syscall only exists in the TAKO VM, so these check the values
+//! that would be logged.
+
+#![cfg(feature = "oom-diagnostics")]
+
+use core::alloc::Layout;
+use tos_alloc::{oom_report, AllocFailure, OOM_LOG_TAG};
+
+#[test]
+fn test_report_values() {
+    let layout = Layout::from_size_align(0x2000, 8).unwrap();
+    assert_eq!(
+        oom_report(Some(AllocFailure::OutOfMemory), &layout, 0x1f40, 0x1f80),
+        [0x4f4f_4d00_0000_0001, 0x2000, 0x8, 0x1f40, 0x1f80]
+    );
+
+    // Every reason keeps the tag in the top bytes
+    let layout = Layout::from_size_align(16, 4096).unwrap();
+    let [tag, size, align, used, peak] =
+        oom_report(Some(AllocFailure::HeapCorrupted), &layout, 0, 0);
+    assert_eq!(tag & !0xff, OOM_LOG_TAG);
+    assert_eq!(tag & 0xff, AllocFailure::HeapCorrupted as u64);
+    assert_eq!((size, align, used, peak), (16, 4096, 0, 0));
+
+    // An unknown reason is logged as 0
+    assert_eq!(oom_report(None, &layout, 8, 24)[0], OOM_LOG_TAG);
+}
@@ -0,0 +1,132 @@
+//! Host tests for the free-list allocator
+//!
+//! The allocator is pointed at a host buffer instead of the TAKO VM heap.
+
+#![cfg(feature = "free-list")]
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{FreeListAllocator, HostRegion};
+
+const HEAP_SIZE: usize = 4096;
+
+#[repr(C, align(4096))]
+struct Heap([u8; HEAP_SIZE]);
+
+fn allocator(heap: &mut Heap) -> FreeListAllocator<HostRegion> {
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), HEAP_SIZE) };
+    FreeListAllocator::with_region(region)
+}
+
+#[test]
+fn test_dealloc_reuses_memory() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(1024, 8).unwrap();
+
+    unsafe {
+        // Far more than the heap holds, unless freed blocks are reused
+        let first = allocator.alloc(layout);
+        assert!(!first.is_null());
+        allocator.dealloc(first, layout);
+
+        for _ in 0..100 {
+            let ptr = allocator.alloc(layout);
+            assert_eq!(ptr, first);
+            allocator.dealloc(ptr, layout);
+        }
+    }
+}
+
+#[test]
+fn test_adjacent_blocks_coalesce() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let small = Layout::from_size_align(1000, 8).unwrap();
+    let large = Layout::from_size_align(3000, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(small);
+        let b = allocator.alloc(small);
+        let c = allocator.alloc(small);
+        assert!(!a.is_null() && !b.is_null() && !c.is_null());
+        assert!(allocator.alloc(large).is_null());
+
+        // Free out of order so both forward and backward merges happen
+        allocator.dealloc(a, small);
+        allocator.dealloc(c, small);
+        allocator.dealloc(b, small);
+
+        let big = allocator.alloc(large);
+        assert_eq!(big, a);
+    }
+}
+
+#[test]
+fn test_alignment() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        for align in [1, 8, 16, 64, 256] {
+            let layout = Layout::from_size_align(24, align).unwrap();
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+        }
+    }
+}
+
+#[test]
+fn test_padding_returns_to_free_list() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let aligned = Layout::from_size_align(16, 1024).unwrap();
+    let small = Layout::from_size_align(16, 8).unwrap();
+
+    unsafe {
+        let big = allocator.alloc(aligned);
+        assert_eq!(big as usize % 1024, 0);
+
+        // The gap skipped for alignment is still available
+        let ptr = allocator.alloc(small);
+        assert!((ptr as usize) < big as usize);
+    }
+}
+
+#[test]
+fn test_out_of_memory_returns_null() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        let layout = Layout::from_size_align(HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        let layout = Layout::from_size_align(512, 8).unwrap();
+        let mut count = 0;
+        while !allocator.alloc(layout).is_null() {
+            count += 1;
+        }
+        assert_eq!(count, 7);
+    }
+}
+
+#[test]
+fn test_tiny_heap_has_no_usable_space() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let header_len = {
+        let probe = allocator(&mut heap);
+        unsafe { probe.alloc(Layout::new::<u8>()) };
+        probe.heap_header().unwrap().header_len as usize
+    };
+    heap.0.fill(0);
+
+    // Room for the allocator state but not for a single block
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), header_len + 4) };
+    let allocator = FreeListAllocator::with_region(region);
+    unsafe {
+        assert_eq!(allocator.usage(), (0, 0));
+        assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        assert_eq!(allocator.usage(), (0, 0));
+    }
+}
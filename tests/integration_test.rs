@@ -1,19 +1,464 @@
 //! Integration tests for tos-alloc
 //!
-//! These tests verify the allocator works correctly with the syscall-based approach.
-//! Note: These tests run in the host environment, not in eBPF VM.
+//! These tests run the allocator selected by cargo feature (`TosAllocator`)
+//! over a `HostHeap`, so they need no TAKO toolchain or VM. End-to-end tests
+//! inside the VM live in `examples/`.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
+use std::cell::Cell;
+use tos_alloc::{
+    AllocFailure, AllocStats, BackendId, FixedRegion, HeapHeader, HeapRegion, HostHeap, HostRegion,
+    OomPolicy, TosAllocator, HEAP_MAGIC, HEAP_VERSION,
+};
+
+const HEAP_SIZE: usize = 8192;
+
+#[cfg(all(feature = "bump", not(feature = "bump-upward")))]
+const BACKEND: BackendId = BackendId::Bump;
+#[cfg(feature = "bump-upward")]
+const BACKEND: BackendId = BackendId::BumpUpward;
+#[cfg(feature = "free-list")]
+const BACKEND: BackendId = BackendId::FreeList;
+#[cfg(feature = "tlsf")]
+const BACKEND: BackendId = BackendId::Tlsf;
+#[cfg(feature = "slab")]
+const BACKEND: BackendId = BackendId::Slab;
+#[cfg(feature = "buddy")]
+const BACKEND: BackendId = BackendId::Buddy;
+
+#[test]
+fn test_alloc_returns_writable_memory_in_heap() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    let layout = Layout::from_size_align(100, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        assert!(!a.is_null() && !b.is_null());
+
+        for ptr in [a, b] {
+            assert!(ptr as usize >= heap.start());
+            assert!(ptr as usize + 100 <= heap.start() + HEAP_SIZE);
+        }
+        assert!(a as usize + 100 <= b as usize || b as usize + 100 <= a as usize);
+
+        a.write_bytes(0xaa, 100);
+        b.write_bytes(0xbb, 100);
+        assert_eq!(*a.add(99), 0xaa);
+        assert_eq!(*b, 0xbb);
+    }
+}
+
+#[test]
+fn test_alloc_respects_alignment() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+
+    unsafe {
+        for shift in 0..=10 {
+            let align = 1 << shift;
+            for size in [1, 3, 24] {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let ptr = allocator.alloc(layout);
+                assert!(!ptr.is_null(), "size {size} align {align}");
+                assert_eq!(ptr as usize % align, 0, "size {size} align {align}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_out_of_memory_returns_null() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+
+    unsafe {
+        // The allocator state takes part of the heap
+        let whole = Layout::from_size_align(HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(whole).is_null());
+
+        let huge = Layout::from_size_align(usize::MAX / 4, 8).unwrap();
+        assert!(allocator.alloc(huge).is_null());
+
+        // Exhaust the heap, then keep failing
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let mut count = 0;
+        while !allocator.alloc(layout).is_null() {
+            count += 1;
+            assert!(count <= HEAP_SIZE / 64);
+        }
+        assert!(count > 0);
+        assert!(allocator.alloc(layout).is_null());
+    }
+}
+
+#[test]
+fn test_invalid_layouts_are_rejected_with_reason() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    assert_eq!(allocator.last_failure(), None);
+
+    let cases = [
+        (HEAP_SIZE + 1, 8, AllocFailure::SizeTooLarge),
+        (usize::MAX / 4, 8, AllocFailure::SizeTooLarge),
+        (isize::MAX as usize - 15, 16, AllocFailure::SizeTooLarge),
+        (8, 2 * HEAP_SIZE, AllocFailure::AlignTooLarge),
+        (8, 1 << 40, AllocFailure::AlignTooLarge),
+        (1 << 20, 1 << 40, AllocFailure::AlignTooLarge),
+    ];
+
+    unsafe {
+        let before = allocator.usage();
+        for (size, align, reason) in cases {
+            let layout = Layout::from_size_align(size, align).unwrap();
+            assert!(allocator.alloc(layout).is_null(), "{layout:?}");
+            assert_eq!(allocator.last_failure(), Some(reason), "{layout:?}");
+        }
+
+        // Rejected requests leave the heap untouched
+        assert_eq!(allocator.usage(), before);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        assert!(!allocator.alloc(layout).is_null());
+
+        // Requests that could fit an empty heap fail for lack of space
+        while !allocator.alloc(layout).is_null() {}
+        assert_eq!(allocator.last_failure(), Some(AllocFailure::OutOfMemory));
+    }
+}
 
 #[test]
-fn test_allocator_compiles() {
-    // This test just verifies the allocator code compiles correctly
-    // The actual functionality will be tested in end-to-end tests with the VM
+fn test_alloc_zeroed_clears_reused_memory() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+
+    unsafe {
+        for size in [8, 100, 600] {
+            let layout = Layout::from_size_align(size, 8).unwrap();
+
+            // Fresh memory
+            let ptr = allocator.alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            let bytes = core::slice::from_raw_parts(ptr, size);
+            assert!(bytes.iter().all(|&byte| byte == 0), "size {size}");
+
+            // Memory handed out and dirtied before
+            ptr.write_bytes(0xff, size);
+            allocator.dealloc(ptr, layout);
+            let ptr = allocator.alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            let bytes = core::slice::from_raw_parts(ptr, size);
+            assert!(bytes.iter().all(|&byte| byte == 0), "size {size}");
+
+            // A larger block over the old one and fresh memory
+            let larger = Layout::from_size_align(2 * size, 8).unwrap();
+            let ptr = allocator.realloc(ptr, layout, 2 * size);
+            ptr.write_bytes(0xff, 2 * size);
+            allocator.dealloc(ptr, larger);
+            let ptr = allocator.alloc_zeroed(Layout::from_size_align(3 * size, 8).unwrap());
+            assert!(!ptr.is_null());
+            let bytes = core::slice::from_raw_parts(ptr, 3 * size);
+            assert!(bytes.iter().all(|&byte| byte == 0), "size {size}");
+        }
+    }
 }
 
-// Note: We cannot directly test the allocator in unit tests because:
-// 1. It requires the tos_get_heap_region syscall which only exists in the VM
-// 2. GlobalAlloc cannot be easily mocked
-// 3. Real testing requires compiling to eBPF and running in TAKO VM
-//
-// For real end-to-end testing, see:
-// - tos-alloc/examples/basic/src/lib.rs (full allocator test)
-// - tos-alloc/examples/minimal/src/lib.rs (minimal heap test)
+#[test]
+fn test_usage_tracks_allocations() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+
+    let (used, remaining) = allocator.usage();
+    assert_eq!(used, 0);
+    assert!(remaining > 0 && remaining < HEAP_SIZE);
+    let total = remaining;
+
+    unsafe {
+        let layout = Layout::from_size_align(100, 4).unwrap();
+        let ptr = allocator.alloc(layout);
+        let (used, remaining) = allocator.usage();
+        assert!(used >= 100);
+        assert_eq!(used + remaining, total);
+
+        allocator.dealloc(ptr, layout);
+        assert_eq!(allocator.usage(), (0, total));
+    }
+}
+
+#[test]
+fn test_state_is_initialized_on_first_allocation() {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+
+    // Reading usage does not touch the heap
+    allocator.usage();
+    assert!(heap.as_slice().iter().all(|&byte| byte == 0));
+    assert_eq!(HeapHeader::read(heap.as_slice()), None);
+    assert_eq!(allocator.heap_header(), None);
+
+    unsafe {
+        let layout = Layout::from_size_align(32, 8).unwrap();
+        let ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+
+        let header = HeapHeader::read(heap.as_slice()).unwrap();
+        assert_eq!(allocator.heap_header(), Some(header));
+        assert_eq!(header.magic, HEAP_MAGIC);
+        assert_eq!(header.version, HEAP_VERSION);
+        assert_eq!(header.backend(), Some(BACKEND));
+        assert_eq!(header.region_start, heap.start() as u64);
+        assert_eq!(header.region_len, HEAP_SIZE as u64);
+        assert!(header.header_len as usize >= size_of::<HeapHeader>());
+        assert!(ptr as usize >= heap.start() + header.header_len as usize);
+
+        // The bump allocator keeps its position pointer right after it
+        let mut word = size_of::<HeapHeader>();
+        if cfg!(feature = "stats") {
+            word += size_of::<AllocStats>();
+        }
+        let pos = heap.as_slice()[word..word + size_of::<usize>()]
+            .try_into()
+            .unwrap();
+        let pos = usize::from_ne_bytes(pos);
+        if cfg!(all(feature = "bump", not(feature = "bump-upward"))) {
+            assert_eq!(pos, ptr as usize);
+        } else if cfg!(feature = "bump-upward") {
+            assert_eq!(pos, ptr as usize + 32);
+        }
+    }
+}
+
+#[test]
+fn test_region_size_is_queried_once() {
+    /// Region counting how often its size is asked for, like a syscall
+    struct CountingRegion<'a> {
+        heap: &'a HostHeap,
+        queries: Cell<usize>,
+    }
+
+    unsafe impl HeapRegion for CountingRegion<'_> {
+        fn start(&self) -> usize {
+            self.heap.start()
+        }
+
+        fn size(&self) -> usize {
+            self.queries.set(self.queries.get() + 1);
+            self.heap.size()
+        }
+    }
+
+    let heap = HostHeap::new(HEAP_SIZE);
+    let region = CountingRegion {
+        heap: &heap,
+        queries: Cell::new(0),
+    };
+    let allocator = TosAllocator::with_region(&region);
+
+    unsafe {
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        let a = allocator.alloc(layout);
+        let b = allocator.alloc(layout);
+        allocator.usage();
+        allocator.dealloc(b, layout);
+        allocator.dealloc(a, layout);
+        let (used, remaining) = allocator.usage();
+        assert_eq!(used, 0);
+        assert!(remaining > 0);
+    }
+    assert_eq!(region.queries.get(), 1);
+    assert_eq!(
+        allocator.heap_header().unwrap().region_len,
+        HEAP_SIZE as u64
+    );
+}
+
+#[test]
+fn test_corrupted_header_stops_allocation() {
+    let mut heap = HostHeap::new(HEAP_SIZE);
+    let layout = Layout::from_size_align(32, 8).unwrap();
+
+    unsafe {
+        let allocator = TosAllocator::with_region(&heap);
+        assert!(!allocator.alloc(layout).is_null());
+    }
+
+    // A stray write over the magic
+    heap.as_mut_slice()[0] ^= 0xff;
+
+    unsafe {
+        let allocator = TosAllocator::with_region(&heap);
+        let before = heap.as_slice().to_vec();
+        assert!(allocator.alloc(layout).is_null());
+        assert_eq!(allocator.last_failure(), Some(AllocFailure::HeapCorrupted));
+        assert_eq!(allocator.heap_header(), None);
+
+        // The failure is reported without writing to the heap, header
+        // included
+        assert_eq!(heap.as_slice(), &before[..]);
+    }
+}
+
+#[test]
+fn test_wiped_magic_does_not_reinitialize_the_heap() {
+    let mut heap = HostHeap::new(HEAP_SIZE);
+    let layout = Layout::from_size_align(32, 8).unwrap();
+
+    let used = unsafe {
+        let allocator = TosAllocator::with_region(&heap);
+        assert!(!allocator.alloc(layout).is_null());
+        allocator.usage().0
+    };
+
+    // A stray write zeroing the magic, leaving the rest of the header
+    heap.as_mut_slice()[..4].fill(0);
+
+    unsafe {
+        let allocator = TosAllocator::with_region(&heap);
+        let before = heap.as_slice().to_vec();
+        assert!(allocator.alloc(layout).is_null());
+        assert_eq!(allocator.last_failure(), Some(AllocFailure::HeapCorrupted));
+        assert_eq!(heap.as_slice(), &before[..]);
+    }
+
+    // Restoring the magic finds the live heap as it was
+    heap.as_mut_slice()[..4].copy_from_slice(&HEAP_MAGIC.to_le_bytes());
+    assert_eq!(TosAllocator::with_region(&heap).usage().0, used);
+}
+
+#[test]
+fn test_region_holding_only_the_heap_header_is_not_overrun() {
+    let mut heap = HostHeap::new(HEAP_SIZE);
+    let len = size_of::<HeapHeader>();
+    // Bytes past the region, which the allocator must never touch
+    heap.as_mut_slice()[len..].fill(0xaa);
+
+    let region = unsafe { HostRegion::new(heap.as_mut_slice().as_mut_ptr(), len) };
+    let allocator = TosAllocator::with_region(region);
+    unsafe {
+        assert_eq!(allocator.usage(), (0, 0));
+        assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        assert_eq!(allocator.last_failure(), Some(AllocFailure::SizeTooLarge));
+        assert_eq!(allocator.heap_header(), None);
+        assert_eq!(allocator.usage(), (0, 0));
+    }
+    assert!(heap.as_slice()[len..].iter().all(|&byte| byte == 0xaa));
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats_count_allocations() {
+    use tos_alloc::HEAP_FLAG_STATS;
+
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    assert_eq!(allocator.stats(), AllocStats::default());
+
+    unsafe {
+        let small = Layout::from_size_align(24, 8).unwrap();
+        let large = Layout::from_size_align(1000, 8).unwrap();
+        let a = allocator.alloc(small);
+        let b = allocator.alloc(large);
+        let peak = allocator.usage().0;
+        let b = allocator.realloc(b, large, 10);
+        assert!(!a.is_null() && !b.is_null());
+
+        let failed = Layout::from_size_align(HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(failed).is_null());
+        assert!(allocator.realloc(a, small, HEAP_SIZE - 8).is_null());
+
+        allocator.dealloc(b, Layout::from_size_align(10, 8).unwrap());
+        allocator.dealloc(a, small);
+
+        let stats = allocator.stats();
+        assert_eq!(stats.allocs, 2);
+        assert_eq!(stats.deallocs, 2);
+        assert_eq!(stats.reallocs, 1);
+        assert_eq!(stats.failures, 2);
+        assert_eq!(stats.largest_failure, HEAP_SIZE as u64);
+        assert_eq!(stats.last_failure_size, HEAP_SIZE as u64 - 8);
+        assert_eq!(stats.last_failure_align, 8);
+        assert!(stats.peak >= peak as u64);
+        assert!(stats.peak <= HEAP_SIZE as u64);
+        assert!(stats.padding < HEAP_SIZE as u64);
+
+        // Host tools read the same record from a heap dump
+        let header = HeapHeader::read(heap.as_slice()).unwrap();
+        assert_ne!(header.flags & HEAP_FLAG_STATS, 0);
+        assert_eq!(AllocStats::read(heap.as_slice()), Some(stats));
+    }
+}
+
+#[test]
+fn test_allocator_can_own_its_heap() {
+    let allocator = TosAllocator::with_region(HostHeap::new(HEAP_SIZE));
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+        ptr.write_bytes(0x5a, 16);
+        allocator.dealloc(ptr, layout);
+    }
+}
+
+#[test]
+fn test_oom_handler_frees_and_retries() {
+    thread_local! {
+        static ALLOCATOR: Cell<Option<&'static TosAllocator<HostHeap>>> = const { Cell::new(None) };
+        static CACHE: Cell<Option<*mut u8>> = const { Cell::new(None) };
+        static CALLS: Cell<usize> = const { Cell::new(0) };
+    }
+    const LAYOUT: Layout = unsafe { Layout::from_size_align_unchecked(64, 8) };
+
+    // Drops the cached block, if any, and asks for a retry
+    fn drop_cache(layout: Layout, failure: AllocFailure) -> bool {
+        assert_eq!(layout, LAYOUT);
+        assert_eq!(failure, AllocFailure::OutOfMemory);
+        CALLS.set(CALLS.get() + 1);
+        match CACHE.take() {
+            Some(ptr) => {
+                let allocator = ALLOCATOR.get().unwrap();
+                unsafe { allocator.dealloc(ptr, LAYOUT) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    let allocator = TosAllocator::with_region(HostHeap::new(HEAP_SIZE))
+        .with_oom_policy(OomPolicy::Handler(drop_cache));
+    let allocator: &'static _ = Box::leak(Box::new(allocator));
+    ALLOCATOR.set(Some(allocator));
+
+    unsafe {
+        // Fill the heap; the handler has nothing to free yet
+        let mut last = core::ptr::null_mut();
+        loop {
+            let ptr = allocator.alloc(LAYOUT);
+            if ptr.is_null() {
+                break;
+            }
+            last = ptr;
+        }
+        assert_eq!(CALLS.get(), 1);
+
+        // Now the most recent block is a cache the handler can drop
+        CACHE.set(Some(last));
+        assert_eq!(allocator.alloc(LAYOUT), last);
+        assert_eq!(CALLS.get(), 2);
+        assert_eq!(CACHE.get(), None);
+
+        // Retries stop once the handler gives up
+        assert!(allocator.alloc(LAYOUT).is_null());
+        assert_eq!(CALLS.get(), 3);
+    }
+}
+
+#[test]
+fn test_oom_policy_is_set_in_const_context() {
+    static ALLOCATOR: TosAllocator<FixedRegion<0x300000000, 4096>> =
+        TosAllocator::with_region(FixedRegion::new()).with_oom_policy(OomPolicy::Abort);
+    // Compiling is the test; the heap is not mapped on the host
+    let _ = &ALLOCATOR;
+}
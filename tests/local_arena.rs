@@ -0,0 +1,79 @@
+//! Host tests for local arenas
+//!
+//! On the host the arena's chunk comes from the system allocator. The
+//! `Allocator` tests need nightly: `cargo +nightly test --features allocator-api`.
+
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
+
+use core::alloc::Layout;
+use tos_alloc::LocalArena;
+
+#[test]
+fn test_alloc_layout_bumps_within_chunk() {
+    let mut arena = LocalArena::new(256).unwrap();
+    assert_eq!(arena.capacity(), 256);
+    let start = arena.alloc_layout(Layout::new::<u8>()).unwrap();
+
+    // Alignment is padded from the chunk start
+    let word = arena.alloc_layout(Layout::new::<u64>()).unwrap();
+    assert_eq!(word.as_ptr() as usize % 8, 0);
+    assert_eq!(word.as_ptr() as usize - start.as_ptr() as usize, 8);
+    assert_eq!(arena.used(), 16);
+
+    // Over-aligned requests work too
+    let page = arena.alloc_layout(Layout::from_size_align(8, 64).unwrap());
+    assert_eq!(page.unwrap().as_ptr() as usize % 64, 0);
+
+    // Requests beyond the chunk fail, leaving it usable
+    assert!(arena
+        .alloc_layout(Layout::array::<u8>(256).unwrap())
+        .is_none());
+    assert!(arena.alloc_layout(Layout::new::<u64>()).is_some());
+
+    arena.reset();
+    assert_eq!(arena.used(), 0);
+    assert_eq!(arena.alloc_layout(Layout::new::<u8>()), Some(start));
+}
+
+#[test]
+fn test_oversized_arena_is_refused() {
+    assert!(LocalArena::new(usize::MAX).is_none());
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn test_collections_live_in_arena() {
+    let arena = LocalArena::new(4096).unwrap();
+
+    let mut numbers = Vec::new_in(&arena);
+    for i in 0..100u32 {
+        numbers.push(i);
+    }
+    assert_eq!(numbers.iter().sum::<u32>(), 4950);
+
+    // The vector was the only allocation, so growing it reused its block
+    assert!(arena.used() < 2 * 100 * 4);
+
+    let boxed = Box::new_in([7u8; 32], &arena);
+    assert_eq!(boxed[31], 7);
+    let addr = &*boxed as *const _ as usize;
+    let chunk = numbers.as_ptr() as usize;
+    assert!(addr > chunk && addr < chunk + arena.capacity());
+}
+
+#[cfg(feature = "allocator-api")]
+#[test]
+fn test_dropping_latest_collection_returns_space() {
+    let arena = LocalArena::new(1024).unwrap();
+    let kept = Box::new_in(1u64, &arena);
+    let used = arena.used();
+
+    for _ in 0..100 {
+        let mut scratch = Vec::with_capacity_in(64, &arena);
+        scratch.extend_from_slice(&[0u8; 64]);
+        scratch.shrink_to(16);
+        drop(scratch);
+        assert_eq!(arena.used(), used);
+    }
+    assert_eq!(*kept, 1);
+}
@@ -0,0 +1,387 @@
+//! Randomized differential tests against a reference model
+//!
+//! Random alloc/dealloc/realloc sequences run against the allocator selected
+//! by cargo feature over a `HostHeap`. A simple model tracks the live blocks
+//! and their contents; after every step the allocator must agree with it:
+//! blocks in bounds, aligned, non-overlapping and intact, and `usage()`
+//! consistent with what is live. The bump model replays the allocator's
+//! position, so for bump `used` is predicted exactly; backends that reclaim
+//! every block must report less `used` after each free.
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{HeapRegion, HostHeap, TosAllocator};
+
+const HEAP_SIZE: usize = 8192;
+const SEEDS: u64 = 64;
+const STEPS: usize = 1000;
+
+/// Whether freeing every block returns `usage()` to zero
+const RECLAIMS_ALL: bool = cfg!(any(
+    feature = "free-list",
+    feature = "tlsf",
+    feature = "buddy"
+));
+
+/// Xorshift generator, so failures reproduce from the seed alone
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Mostly small sizes, some large ones, rarely more than the heap
+    fn size(&mut self) -> usize {
+        match self.below(16) {
+            0 => 1 + self.below(2 * HEAP_SIZE),
+            1..=3 => 1 + self.below(1024),
+            _ => 1 + self.below(128),
+        }
+    }
+
+    /// Mostly word alignment, sometimes anything up to a page
+    fn align(&mut self) -> usize {
+        match self.below(8) {
+            0 => 1 << self.below(13),
+            1 => 1 << self.below(4),
+            _ => 8,
+        }
+    }
+}
+
+struct Block {
+    ptr: *mut u8,
+    layout: Layout,
+    fill: u8,
+}
+
+/// Bytes the bump allocator takes for `size`, in whole words
+#[cfg(feature = "bump")]
+fn word_size(size: usize) -> usize {
+    (size + 7) & !7
+}
+
+/// Position of the bump allocator, replayed from its rules so `used` can be
+/// predicted to the byte
+#[cfg(feature = "bump")]
+struct BumpModel {
+    base: usize,
+    end: usize,
+    pos: usize,
+}
+
+#[cfg(feature = "bump")]
+impl BumpModel {
+    fn new(heap: &HostHeap, remaining: usize) -> Self {
+        let end = (heap.start() + heap.size()) & !7;
+        let base = end - remaining;
+        let pos = if cfg!(feature = "bump-upward") {
+            base
+        } else {
+            end
+        };
+        Self { base, end, pos }
+    }
+
+    fn used(&self) -> usize {
+        if cfg!(feature = "bump-upward") {
+            self.pos - self.base
+        } else {
+            self.end - self.pos
+        }
+    }
+
+    /// Where `layout` must be placed, or `None` if the request must fail
+    fn alloc(&mut self, layout: Layout) -> Option<usize> {
+        if layout.size() > self.end - self.base {
+            return None;
+        }
+        if cfg!(feature = "bump-upward") {
+            let start = (self.pos + layout.align() - 1) & !(layout.align() - 1);
+            let end = start + word_size(layout.size());
+            if end > self.end {
+                return None;
+            }
+            self.pos = end;
+            Some(start)
+        } else {
+            let pos = self.pos.checked_sub(word_size(layout.size()))? & !(layout.align() - 1);
+            if pos < self.base {
+                return None;
+            }
+            self.pos = pos;
+            Some(pos)
+        }
+    }
+
+    /// Only the most recent block is given back
+    fn dealloc(&mut self, addr: usize, layout: Layout) {
+        let top = addr + word_size(layout.size());
+        if cfg!(feature = "bump-upward") && top == self.pos {
+            self.pos = addr;
+        } else if cfg!(not(feature = "bump-upward")) && addr == self.pos {
+            self.pos = top;
+        }
+    }
+
+    /// Where the resized block must be, or `None` if the request must fail
+    fn realloc(&mut self, addr: usize, layout: Layout, new_size: usize) -> Option<usize> {
+        if new_size > self.end - self.base {
+            return None;
+        }
+        let top = addr + word_size(layout.size());
+        if cfg!(feature = "bump-upward") && top == self.pos {
+            // The most recent block moves its end
+            let end = addr + word_size(new_size);
+            if end > self.end {
+                return None;
+            }
+            self.pos = end;
+            return Some(addr);
+        }
+        if new_size <= layout.size() {
+            return Some(addr);
+        }
+        if cfg!(not(feature = "bump-upward")) && addr == self.pos {
+            // The most recent block grows down from its top
+            let pos = top.checked_sub(word_size(new_size))? & !(layout.align() - 1);
+            if pos < self.base {
+                return None;
+            }
+            self.pos = pos;
+            return Some(pos);
+        }
+        self.alloc(Layout::from_size_align(new_size, layout.align()).unwrap())
+    }
+}
+
+/// Reference model: the blocks that should be live and their contents
+struct Model<'a> {
+    heap: &'a HostHeap,
+    allocator: &'a TosAllocator<&'a HostHeap>,
+    blocks: Vec<Block>,
+    total: usize,
+    next_fill: u8,
+    #[cfg(feature = "bump")]
+    bump: BumpModel,
+}
+
+impl<'a> Model<'a> {
+    fn new(heap: &'a HostHeap, allocator: &'a TosAllocator<&'a HostHeap>) -> Self {
+        let (used, remaining) = allocator.usage();
+        assert_eq!(used, 0);
+        Self {
+            heap,
+            allocator,
+            blocks: Vec::new(),
+            total: remaining,
+            next_fill: 0,
+            #[cfg(feature = "bump")]
+            bump: BumpModel::new(heap, remaining),
+        }
+    }
+
+    /// Allocate `layout` and track the block if the allocator provides one
+    unsafe fn alloc(&mut self, layout: Layout, context: &str) -> bool {
+        let ptr = self.allocator.alloc(layout);
+        #[cfg(feature = "bump")]
+        assert_eq!(
+            (!ptr.is_null()).then_some(ptr as usize),
+            self.bump.alloc(layout),
+            "{context}: alloc {layout:?} placed differently"
+        );
+        if ptr.is_null() {
+            return false;
+        }
+        self.insert(ptr, layout, context);
+        true
+    }
+
+    /// Free the block at `index`
+    unsafe fn dealloc(&mut self, index: usize, context: &str) {
+        let block = self.blocks.remove(index);
+        let (before, _) = self.allocator.usage();
+        self.allocator.dealloc(block.ptr, block.layout);
+        #[cfg(feature = "bump")]
+        self.bump.dealloc(block.ptr as usize, block.layout);
+
+        if RECLAIMS_ALL {
+            let (used, _) = self.allocator.usage();
+            assert!(used < before, "{context}: dealloc left used at {used}");
+        }
+    }
+
+    /// Resize the block at `index`, keeping it if the allocator refuses
+    unsafe fn realloc(&mut self, index: usize, new_size: usize, context: &str) {
+        let block = self.blocks.remove(index);
+        let ptr = self.allocator.realloc(block.ptr, block.layout, new_size);
+        #[cfg(feature = "bump")]
+        assert_eq!(
+            (!ptr.is_null()).then_some(ptr as usize),
+            self.bump
+                .realloc(block.ptr as usize, block.layout, new_size),
+            "{context}: realloc {:?} to {new_size} placed differently",
+            block.layout
+        );
+
+        if ptr.is_null() {
+            // The old block must be untouched and still owned
+            self.blocks.insert(index, block);
+            return;
+        }
+        let kept = block.layout.size().min(new_size);
+        let bytes = core::slice::from_raw_parts(ptr, kept);
+        assert!(
+            bytes.iter().all(|&byte| byte == block.fill),
+            "{context}: realloc lost contents"
+        );
+        let layout = Layout::from_size_align(new_size, block.layout.align()).unwrap();
+        self.insert(ptr, layout, context);
+    }
+
+    /// Check a block handed out by the allocator and start tracking it
+    unsafe fn insert(&mut self, ptr: *mut u8, layout: Layout, context: &str) {
+        let addr = ptr as usize;
+        let start = self.heap.start();
+        assert!(addr >= start, "{context}: {addr:#x} below heap");
+        assert!(
+            addr + layout.size() <= start + self.heap.size(),
+            "{context}: {addr:#x}+{} beyond heap",
+            layout.size()
+        );
+        assert_eq!(addr % layout.align(), 0, "{context}: misaligned {layout:?}");
+        for block in &self.blocks {
+            let other = block.ptr as usize;
+            assert!(
+                addr + layout.size() <= other || other + block.layout.size() <= addr,
+                "{context}: {addr:#x} {layout:?} overlaps {other:#x} {:?}",
+                block.layout
+            );
+        }
+
+        self.next_fill = self.next_fill.wrapping_add(1).max(1);
+        ptr.write_bytes(self.next_fill, layout.size());
+        self.blocks.push(Block {
+            ptr,
+            layout,
+            fill: self.next_fill,
+        });
+    }
+
+    /// Every live block still holds what was written to it
+    unsafe fn verify(&self, context: &str) {
+        for block in &self.blocks {
+            let bytes = core::slice::from_raw_parts(block.ptr, block.layout.size());
+            assert!(
+                bytes.iter().all(|&byte| byte == block.fill),
+                "{context}: block {:p} {:?} corrupted",
+                block.ptr,
+                block.layout
+            );
+        }
+
+        let (used, remaining) = self.allocator.usage();
+        let live: usize = self.blocks.iter().map(|block| block.layout.size()).sum();
+        assert_eq!(
+            used + remaining,
+            self.total,
+            "{context}: usage {used}+{remaining}"
+        );
+        assert!(used >= live, "{context}: used {used} < live {live}");
+        #[cfg(feature = "bump")]
+        assert_eq!(used, self.bump.used(), "{context}: used differs from model");
+        if RECLAIMS_ALL && self.blocks.is_empty() {
+            assert_eq!(used, 0, "{context}: memory not reclaimed");
+        }
+    }
+}
+
+unsafe fn run(seed: u64) {
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    let mut model = Model::new(&heap, &allocator);
+    let mut rng = Rng::new(seed);
+
+    for step in 0..STEPS {
+        let context = format!("seed {seed} step {step}");
+        let action = rng.below(10);
+
+        if action < 4 || model.blocks.is_empty() {
+            let layout = Layout::from_size_align(rng.size(), rng.align()).unwrap();
+            model.alloc(layout, &context);
+        } else if action < 8 {
+            // Mostly free the newest block, which every backend can reclaim
+            let index = if rng.below(2) == 0 {
+                model.blocks.len() - 1
+            } else {
+                rng.below(model.blocks.len())
+            };
+            model.dealloc(index, &context);
+        } else {
+            let index = rng.below(model.blocks.len());
+            let new_size = rng.size();
+            model.realloc(index, new_size, &context);
+        }
+
+        model.verify(&context);
+    }
+
+    // Tear down newest first, so LIFO backends reclaim as much as they can
+    let context = format!("seed {seed} teardown");
+    while !model.blocks.is_empty() {
+        model.dealloc(model.blocks.len() - 1, &context);
+        model.verify(&context);
+    }
+    if RECLAIMS_ALL {
+        assert_eq!(allocator.usage().0, 0, "{context}: memory not reclaimed");
+    }
+}
+
+#[test]
+fn test_random_sequences_match_model() {
+    for seed in 0..SEEDS {
+        unsafe { run(seed) };
+    }
+}
+
+#[test]
+fn test_lifo_sequences_reclaim_everything() {
+    // Stack-like usage is the one pattern every backend but slab fully
+    // reclaims. Slab's freed blocks go back to their size-class lists, but
+    // the padding lost aligning each block as it was carved from the bump
+    // region stays counted as used
+    let heap = HostHeap::new(HEAP_SIZE);
+    let allocator = TosAllocator::with_region(&heap);
+    let mut model = Model::new(&heap, &allocator);
+    let mut rng = Rng::new(SEEDS);
+
+    unsafe {
+        for round in 0..100 {
+            let context = format!("round {round}");
+            for _ in 0..1 + rng.below(8) {
+                let layout = Layout::from_size_align(1 + rng.below(256), 8).unwrap();
+                assert!(model.alloc(layout, &context), "{context}: out of memory");
+                model.verify(&context);
+            }
+            while !model.blocks.is_empty() {
+                model.dealloc(model.blocks.len() - 1, &context);
+                model.verify(&context);
+            }
+        }
+    }
+
+    if cfg!(not(feature = "slab")) {
+        assert_eq!(allocator.usage().0, 0);
+    }
+}
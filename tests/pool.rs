@@ -0,0 +1,96 @@
+//! Host tests for object pools
+//!
+//! On the host the pool's chunk comes from the system allocator.
+
+use std::cell::Cell;
+use tos_alloc::{Pool, PoolBox};
+
+#[test]
+fn test_pool_recycles_slots() {
+    let pool = Pool::new(3).unwrap();
+    let a = pool.alloc(1u64).unwrap();
+    let b = pool.alloc(2u64).unwrap();
+    let c = pool.alloc(3u64).unwrap();
+    assert_eq!(pool.len(), 3);
+
+    // A full pool hands further values back
+    assert_eq!(pool.alloc(4).err(), Some(4));
+
+    // Freed slots are handed out again, most recently freed first
+    let addr = &*b as *const u64;
+    drop(b);
+    let d = pool.alloc(5).unwrap();
+    assert_eq!(&*d as *const u64, addr);
+    assert_eq!(*a + *c + *d, 9);
+
+    assert_eq!(PoolBox::into_inner(a), 1);
+    assert_eq!(pool.len(), 2);
+    let (used, free) = pool.usage();
+    assert_eq!(used, 2 * 8);
+    assert_eq!(free, 8);
+}
+
+#[test]
+fn test_pool_needs_a_slot() {
+    assert!(Pool::<u64>::new(0).is_none());
+    assert!(Pool::<u64>::new(usize::MAX).is_none());
+
+    let pool = Pool::new(1).unwrap();
+    assert_eq!(pool.capacity(), 1);
+    let only = pool.alloc(String::from("first")).unwrap();
+    assert_eq!(
+        pool.alloc(String::from("second")).err().as_deref(),
+        Some("second")
+    );
+    assert_eq!(PoolBox::into_inner(only), "first");
+}
+
+#[test]
+fn test_pool_drops_values() {
+    #[derive(Debug)]
+    struct Record<'a>(&'a Cell<u32>);
+
+    impl Drop for Record<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let drops = Cell::new(0);
+    let pool = Pool::new(2).unwrap();
+    for _ in 0..10 {
+        let first = pool.alloc(Record(&drops)).unwrap();
+        let _second = pool.alloc(Record(&drops)).unwrap();
+        drop(first);
+    }
+    assert_eq!(drops.get(), 20);
+    assert!(pool.is_empty());
+
+    // Values moved out are not dropped by the pool
+    let record = PoolBox::into_inner(pool.alloc(Record(&drops)).unwrap());
+    assert_eq!(drops.get(), 20);
+    drop(record);
+    assert_eq!(drops.get(), 21);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_pool_reports_occupancy() {
+    let pool = Pool::new(4).unwrap();
+    let kept: Vec<_> = (0..3u32).map(|i| pool.alloc(i).unwrap()).collect();
+    drop(pool.alloc(3).unwrap());
+    let extra = pool.alloc(4).unwrap();
+    assert_eq!(pool.alloc(5).err(), Some(5));
+
+    let stats = pool.stats();
+    assert_eq!(stats.allocs, 5);
+    assert_eq!(stats.deallocs, 1);
+    assert_eq!(stats.peak, 4 * 8);
+    // Each `u32` leaves half of its pointer-sized slot unused
+    assert_eq!(stats.padding, 5 * 4);
+    assert_eq!(stats.failures, 1);
+    assert_eq!((stats.last_failure_size, stats.last_failure_align), (4, 4));
+
+    drop((kept, extra));
+    assert_eq!(pool.stats().deallocs, 5);
+}
@@ -0,0 +1,30 @@
+//! Host tests for the heap region providers
+
+use tos_alloc::{
+    FixedRegion, HeapRegion, HostRegion, SyscallRegion, DEFAULT_HEAP_SIZE, HEAP_START,
+};
+
+#[test]
+fn test_syscall_region_falls_back_outside_vm() {
+    let region = SyscallRegion::<HEAP_START, { 64 * 1024 }>::new();
+    assert_eq!(region.start(), HEAP_START);
+    assert_eq!(region.size(), 64 * 1024);
+
+    let region = SyscallRegion::<HEAP_START>::new();
+    assert_eq!(region.size(), DEFAULT_HEAP_SIZE);
+}
+
+#[test]
+fn test_fixed_region_reports_configuration() {
+    let region = FixedRegion::<0x400000000, 4096>::new();
+    assert_eq!(region.start(), 0x400000000);
+    assert_eq!(region.size(), 4096);
+}
+
+#[test]
+fn test_host_region_reports_buffer() {
+    let mut buffer = vec![0u64; 512];
+    let region = unsafe { HostRegion::new(buffer.as_mut_ptr() as *mut u8, 4096) };
+    assert_eq!(region.start(), buffer.as_ptr() as usize);
+    assert_eq!(region.size(), 4096);
+}
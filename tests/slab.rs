@@ -0,0 +1,136 @@
+//! Host tests for the size-class slab allocator
+//!
+//! The allocator is pointed at a host buffer instead of the TAKO VM heap.
+
+#![cfg(feature = "slab")]
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{SlabAllocator, HostRegion};
+
+const HEAP_SIZE: usize = 4096;
+
+#[repr(C, align(4096))]
+struct Heap([u8; HEAP_SIZE]);
+
+fn allocator(heap: &mut Heap) -> SlabAllocator<HostRegion> {
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), HEAP_SIZE) };
+    SlabAllocator::with_region(region)
+}
+
+#[test]
+fn test_freed_blocks_are_reused_by_class() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        // 20 and 32 bytes share the 32-byte class
+        let a = allocator.alloc(Layout::from_size_align(20, 4).unwrap());
+        allocator.dealloc(a, Layout::from_size_align(20, 4).unwrap());
+        let b = allocator.alloc(Layout::from_size_align(32, 8).unwrap());
+        assert_eq!(a, b);
+
+        // A different class does not take it
+        allocator.dealloc(b, Layout::from_size_align(32, 8).unwrap());
+        let c = allocator.alloc(Layout::from_size_align(64, 8).unwrap());
+        assert_ne!(b, c);
+    }
+}
+
+#[test]
+fn test_churn_fits_in_heap() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        // Allocates ~100x the heap size in small nodes
+        for round in 0..1000 {
+            let layouts = [
+                Layout::from_size_align(8, 8).unwrap(),
+                Layout::from_size_align(48, 8).unwrap(),
+                Layout::from_size_align(200 + round % 50, 8).unwrap(),
+            ];
+            let ptrs = layouts.map(|layout| allocator.alloc(layout));
+            for (ptr, layout) in ptrs.into_iter().zip(layouts) {
+                assert!(!ptr.is_null());
+                allocator.dealloc(ptr, layout);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_blocks_are_aligned_to_class() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        for (size, align) in [(1, 1), (3, 2), (24, 8), (16, 16), (100, 64), (8, 256)] {
+            let ptr = allocator.alloc(Layout::from_size_align(size, align).unwrap());
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+        }
+    }
+}
+
+#[test]
+fn test_large_requests_use_bump_region() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let large = Layout::from_size_align(1300, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(large);
+        assert!(!a.is_null());
+        allocator.dealloc(a, large);
+
+        // Large blocks are not recycled
+        let b = allocator.alloc(large);
+        assert!(!b.is_null());
+        assert_ne!(a, b);
+
+        let c = allocator.alloc(large);
+        assert!(!c.is_null());
+        assert!(allocator.alloc(large).is_null());
+    }
+}
+
+#[test]
+fn test_remaining_excludes_lost_padding() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        // An odd size followed by an over-aligned block leaves a gap
+        let odd = allocator.alloc(Layout::from_size_align(1001, 1).unwrap());
+        let aligned = allocator.alloc(Layout::from_size_align(600, 256).unwrap());
+        assert!(!odd.is_null() && !aligned.is_null());
+        let (used, remaining) = allocator.usage();
+        assert!(used > 1601);
+
+        // Exactly the remaining bytes can still be allocated
+        let rest = Layout::from_size_align(remaining, 1).unwrap();
+        assert!(!allocator.alloc(rest).is_null());
+        assert_eq!(allocator.usage().1, 0);
+    }
+}
+
+#[test]
+fn test_tiny_heap_has_no_usable_space() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let header_len = {
+        let probe = allocator(&mut heap);
+        unsafe { probe.alloc(Layout::new::<u8>()) };
+        probe.heap_header().unwrap().header_len as usize
+    };
+    heap.0.fill(0);
+
+    // Room for the allocator state but not for a single block
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), header_len + 4) };
+    let allocator = SlabAllocator::with_region(region);
+    unsafe {
+        // The spare bytes are reported, but fit no size class
+        assert_eq!(allocator.usage(), (0, 4));
+        assert!(allocator.alloc(Layout::new::<u8>()).is_null());
+        assert_eq!(allocator.usage(), (0, 4));
+    }
+}
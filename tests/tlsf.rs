@@ -0,0 +1,154 @@
+//! Host tests for the TLSF allocator
+//!
+//! The allocator is pointed at a host buffer instead of the TAKO VM heap.
+
+#![cfg(feature = "tlsf")]
+
+use core::alloc::{GlobalAlloc, Layout};
+use tos_alloc::{TlsfAllocator, HostRegion};
+
+const HEAP_SIZE: usize = 8192;
+
+#[repr(C, align(4096))]
+struct Heap([u8; HEAP_SIZE]);
+
+fn allocator(heap: &mut Heap) -> TlsfAllocator<HostRegion> {
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), HEAP_SIZE) };
+    TlsfAllocator::with_region(region)
+}
+
+#[test]
+fn test_dealloc_reuses_memory() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let layout = Layout::from_size_align(2048, 8).unwrap();
+
+    unsafe {
+        let first = allocator.alloc(layout);
+        assert!(!first.is_null());
+        allocator.dealloc(first, layout);
+
+        for _ in 0..100 {
+            let ptr = allocator.alloc(layout);
+            assert_eq!(ptr, first);
+            allocator.dealloc(ptr, layout);
+        }
+    }
+}
+
+#[test]
+fn test_adjacent_blocks_coalesce() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let small = Layout::from_size_align(2000, 8).unwrap();
+    let large = Layout::from_size_align(6000, 8).unwrap();
+
+    unsafe {
+        let a = allocator.alloc(small);
+        let b = allocator.alloc(small);
+        let c = allocator.alloc(small);
+        assert!(!a.is_null() && !b.is_null() && !c.is_null());
+        assert!(allocator.alloc(large).is_null());
+
+        // Free out of order so both forward and backward merges happen
+        allocator.dealloc(a, small);
+        allocator.dealloc(c, small);
+        allocator.dealloc(b, small);
+
+        assert!(!allocator.alloc(large).is_null());
+    }
+}
+
+#[test]
+fn test_alignment() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        for align in [1, 2, 8, 16, 64, 256, 1024] {
+            let layout = Layout::from_size_align(24, align).unwrap();
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            assert_eq!(ptr as usize % align, 0);
+        }
+    }
+}
+
+#[test]
+fn test_out_of_memory_returns_null() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+
+    unsafe {
+        let layout = Layout::from_size_align(HEAP_SIZE, 8).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        let layout = Layout::from_size_align(usize::MAX / 4, 8).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+
+        let layout = Layout::from_size_align(8, 1 << 20).unwrap();
+        assert!(allocator.alloc(layout).is_null());
+    }
+}
+
+#[test]
+fn test_churn_blocks_do_not_overlap() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let allocator = allocator(&mut heap);
+    let mut live: Vec<(*mut u8, Layout)> = Vec::new();
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+
+    unsafe {
+        for _ in 0..2000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+
+            if seed.is_multiple_of(3) && !live.is_empty() {
+                let (ptr, layout) = live.swap_remove(seed as usize % live.len());
+                allocator.dealloc(ptr, layout);
+                continue;
+            }
+
+            let layout = Layout::from_size_align(1 + (seed >> 8) as usize % 300, 8).unwrap();
+            let ptr = allocator.alloc(layout);
+            if ptr.is_null() {
+                continue;
+            }
+            for &(other, other_layout) in &live {
+                let disjoint = ptr as usize + layout.size() <= other as usize
+                    || other as usize + other_layout.size() <= ptr as usize;
+                assert!(disjoint);
+            }
+            live.push((ptr, layout));
+        }
+
+        for (ptr, layout) in live.drain(..) {
+            allocator.dealloc(ptr, layout);
+        }
+
+        // Everything merged back into one block
+        let layout = Layout::from_size_align(6000, 8).unwrap();
+        assert!(!allocator.alloc(layout).is_null());
+    }
+}
+
+#[test]
+fn test_tiny_heap_has_no_usable_space() {
+    let mut heap = Box::new(Heap([0; HEAP_SIZE]));
+    let header_len = {
+        let probe = allocator(&mut heap);
+        unsafe { probe.alloc(Layout::new::<u8>()) };
+        probe.heap_header().unwrap().header_len as usize
+    };
+    heap.0.fill(0);
+
+    // Room for the allocator state but not for a single block
+    let region = unsafe { HostRegion::new(heap.0.as_mut_ptr(), header_len + 4) };
+    let allocator = TlsfAllocator::with_region(region);
+    unsafe {
+        assert_eq!(allocator.usage(), (0, 0));
+        assert!(allocator.alloc(Layout::new::<u64>()).is_null());
+        assert_eq!(allocator.usage(), (0, 0));
+    }
+}